@@ -1,23 +1,59 @@
-use advent_of_code_2023::day02::{Game, RGBCount};
-use std::io::{self, BufRead};
+use advent_of_code_2023::day02::*;
+use std::io;
 
+/// With no arguments, sums the games possible with the puzzle's bag.
+/// `query <bag>...` reports which games each bag allows, where bags look like `12 red, 13 green`.
 fn main() {
-    let stdin = io::stdin();
-    let mut sum: u32 = 0;
-    let bag = RGBCount { red: 12, green: 13, blue: 14 };
-    for line in stdin.lock().lines() {
-        let game = Game::parse(&line.unwrap());
-        if is_possible(&bag, &game) {
-            sum += game.num;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let input = io::read_to_string(io::stdin()).expect("IO Error");
+    let games = parse_games(&input).expect("Invalid games");
+    match args.first().map(String::as_str) {
+        None => {
+            let bag = ColorCount::rgb(12, 13, 14);
+            println!("{}", day02(&games, &bag));
         }
+        Some("query") => {
+            let bags: Vec<ColorCount> = args[1..]
+                .iter()
+                .map(|spec| ColorCount::parse(spec).expect("Invalid bag"))
+                .collect();
+            assert!(!bags.is_empty(), "query needs at least one bag");
+            query(&games, &bags);
+        }
+        Some(_) => panic!("Usage: day02 [query <bag>...]"),
     }
-    println!("{}", sum);
 }
 
-fn is_possible(bag: &RGBCount, game: &Game) -> bool {
-    game.draws.iter().all(|draw| draw.red <= bag.red && draw.green <= bag.green && draw.blue <= bag.blue)
+fn day02(games: &[Game], bag: &ColorCount) -> u32 {
+    games
+        .iter()
+        .filter(|game| game.is_feasible(bag))
+        .map(|game| game.num)
+        .sum()
 }
 
+fn query(games: &[Game], bags: &[ColorCount]) {
+    for (bag, feasible) in bags.iter().zip(feasible_games(games, bags)) {
+        println!("bag {:?}", bag.iter().collect::<Vec<_>>());
+        println!("  possible games: {:?}", feasible);
+        println!("  sum: {}", feasible.iter().sum::<u32>());
+        for game in games {
+            if let Some(infeasibility) = game.infeasible_draw(bag) {
+                println!(
+                    "  game {} draw {} is short of {:?}",
+                    game.num,
+                    infeasibility.draw_idx + 1,
+                    infeasibility.shortfalls
+                );
+            }
+        }
+    }
+    let min_bag = min_bag_for_all(games);
+    println!(
+        "smallest bag for every game: {:?}",
+        min_bag.iter().collect::<Vec<_>>()
+    );
+}
 
 #[cfg(test)]
 mod tests {
@@ -25,12 +61,13 @@ mod tests {
 
     #[test]
     fn examples() {
-        let bag = RGBCount { red: 12, green: 13, blue: 14 };
-        assert_eq!(is_possible( &bag, &Game::parse("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green")), true);
-        assert_eq!(is_possible( &bag, &Game::parse("Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue")), true);
-        assert_eq!(is_possible( &bag, &Game::parse("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red")), false);
-        assert_eq!(is_possible( &bag, &Game::parse("Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red")), false);
-        assert_eq!(is_possible( &bag, &Game::parse("Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green")), true);
+        let bag = ColorCount::rgb(12, 13, 14);
+        let game = |line| Game::parse(line).unwrap();
+        assert_eq!(game("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").is_feasible(&bag), true);
+        assert_eq!(game("Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue").is_feasible(&bag), true);
+        assert_eq!(game("Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red").is_feasible(&bag), false);
+        assert_eq!(game("Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red").is_feasible(&bag), false);
+        assert_eq!(game("Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green").is_feasible(&bag), true);
         // total = 8
     }
 }
@@ -1,9 +1,31 @@
-use advent_of_code_2023::day04::day04b;
+use advent_of_code_2023::day04::*;
 use std::io::{self, BufRead};
 
 fn main() {
+    let arg = std::env::args().nth(1);
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.unwrap());
-    let result = day04b(lines);
-    println!("{}", result);
+    let cards: Vec<Card> = lines.map(|line| Card::from_line(&line)).collect();
+    match arg.as_deref() {
+        None => {}
+        Some("provenance") => {
+            let provenance = copy_provenance(&cards).expect("Invalid scratchcards");
+            for card in &provenance {
+                println!(
+                    "Card {}: won {} copies from {:?}, generated {}",
+                    card.id, card.won, card.won_from, card.generated
+                );
+            }
+            if let Some(top) = provenance.iter().max_by_key(|card| card.generated) {
+                println!("Card {} generated the most copies", top.id);
+            }
+        }
+        Some("tree") => {
+            let tree = render_cascade_tree(&cards, 1000).expect("Can't draw the cascade");
+            println!("{}", tree);
+        }
+        Some(_) => panic!("Argument must be one of: provenance, tree"),
+    }
+    let counts = cascade_counts(&cards).expect("Invalid scratchcards");
+    println!("{}", counts.iter().sum::<u32>());
 }
@@ -0,0 +1,18 @@
+use advent_of_code_2023::day07::*;
+use std::io::{self, BufRead};
+
+fn main() {
+    let format = match std::env::args().nth(1).as_deref() {
+        None => None,
+        Some("text") => Some(TableFormat::Text),
+        Some("csv") => Some(TableFormat::Csv),
+        Some(_) => panic!("Argument must be one of: text, csv"),
+    };
+    let stdin = io::stdin();
+    let lines = stdin.lock().lines().map(|l| l.unwrap());
+    let hands = rank_hands(lines, &Ruleset::joker_wild());
+    if let Some(format) = format {
+        println!("{}", render_standings(&standings(&hands), format));
+    }
+    println!("{}", total_winnings(&hands));
+}
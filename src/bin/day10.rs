@@ -1,17 +1,20 @@
-use advent_of_code_2023::{cursor_grid::*, day10::*};
+use advent_of_code_2023::day10::*;
 use std::io::{self, BufRead};
 
-type Num = u32;
-
 fn main() {
+    let show_overlay = std::env::args().nth(1).as_deref() == Some("overlay");
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.expect("IO Error"));
     let result = day10(lines);
-    println!("{}", result);
+    if show_overlay {
+        println!("{}", result.render_overlay());
+        println!("loop length: {}", result.loop_length);
+        println!("farthest tiles: {:?}", result.farthest);
+    }
+    println!("{}", result.farthest_distance);
 }
 
-fn day10(lines: impl Iterator<Item = impl AsRef<str>>) -> Num {
-    let (grid, start_pos) = parse_grid(lines);
-    let pipe_loop_iter = PipeLoopIterator::new(start_pos, &grid);
-    pipe_loop_iter.count() as Num / 2
+fn day10(lines: impl Iterator<Item = impl AsRef<str>>) -> LoopDistances {
+    let (grid, start_pos) = parse_grid(lines).expect("Invalid maze");
+    loop_distances(start_pos, &grid).expect("Broken loop")
 }
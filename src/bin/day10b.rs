@@ -1,18 +1,91 @@
-use advent_of_code_2023::{cursor_grid::*, day10::*};
+use advent_of_code_2023::{cursor_grid::*, day10::*, geometry};
 use std::io::{self, BufRead};
 
 type Num = u32;
 
+/// How to count the tiles enclosed by the loop.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Mode {
+    /// Flood fill an upscaled boundary grid.
+    FloodFill,
+    /// Shoelace area of the loop plus Pick's theorem.
+    Pick,
+    /// Run both and make sure they agree.
+    CrossCheck,
+}
+
+impl Mode {
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "flood" => Some(Self::FloodFill),
+            "pick" => Some(Self::Pick),
+            "check" => Some(Self::CrossCheck),
+            _ => None,
+        }
+    }
+}
+
 fn main() {
+    let mut mode = Mode::FloodFill;
+    let mut maybe_charset = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "render" => maybe_charset = Some(Charset::BoxDrawing),
+            "render-ascii" => maybe_charset = Some(Charset::Ascii),
+            _ => {
+                mode = Mode::from_arg(&arg)
+                    .expect("Arguments must be one of: flood, pick, check, render, render-ascii")
+            }
+        }
+    }
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.expect("IO Error"));
-    let result = day10b(lines);
+    let (grid, start_pos) = parse_grid(lines).expect("Invalid maze");
+    if let Some(charset) = maybe_charset {
+        let pipe_loop = walk_loop(start_pos, &grid).expect("Broken loop");
+        let classes = classify_tiles(&grid, &pipe_loop);
+        println!("{}", render_grid(&grid, Some(&classes), charset));
+    }
+    let result = count_enclosed(&grid, start_pos, mode);
     println!("{}", result);
 }
 
-fn day10b(lines: impl Iterator<Item = impl AsRef<str>>) -> Num {
-    let (grid, start_pos) = parse_grid(lines);
-    let pipe_loop_iter = PipeLoopIterator::new(start_pos, &grid);
+fn count_enclosed(grid: &Grid<Option<Pipe>>, start_pos: Coord, mode: Mode) -> Num {
+    match mode {
+        Mode::FloodFill => count_enclosed_flood_fill(grid, start_pos),
+        Mode::Pick => count_enclosed_pick(grid, start_pos),
+        Mode::CrossCheck => {
+            let flood_fill_count = count_enclosed_flood_fill(grid, start_pos);
+            let pick_count = count_enclosed_pick(grid, start_pos);
+            assert_eq!(
+                flood_fill_count, pick_count,
+                "Flood fill and Pick's theorem disagree"
+            );
+            let pipe_loop = walk_loop(start_pos, grid).expect("Broken loop");
+            let scanline_count = classify_tiles(grid, &pipe_loop)
+                .iter()
+                .filter(|class| **class == TileClass::Inside)
+                .count() as Num;
+            assert_eq!(
+                flood_fill_count, scanline_count,
+                "Flood fill and scanline classification disagree"
+            );
+            pick_count
+        }
+    }
+}
+
+fn count_enclosed_pick(grid: &Grid<Option<Pipe>>, start_pos: Coord) -> Num {
+    // Every tile of the loop is a vertex; collinear vertices don't affect the area.
+    let vertices = walk_loop(start_pos, grid).expect("Broken loop");
+    geometry::interior_point_count(&vertices)
+        .try_into()
+        .unwrap()
+}
+
+fn count_enclosed_flood_fill(grid: &Grid<Option<Pipe>>, start_pos: Coord) -> Num {
+    let pipe_loop_iter =
+        PipeLoopIterator::new(start_pos, grid).map(|step| step.expect("Broken loop"));
     let mut boundary_grid = make_boundary_grid(grid.rows[0].len(), grid.rows.len());
     // Draw boundary on boundary grid
     for (pos, pipe) in pipe_loop_iter {
@@ -58,3 +131,29 @@ fn make_boundary_grid(width: usize, height: usize) -> Grid<InOutBoundary> {
     }
     return grid;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &[&str] = &[
+        ".F----7F7F7F7F-7....",
+        ".|F--7||||||||FJ....",
+        ".||.FJ||||||||L7....",
+        "FJL7L7LJLJ||LJ.L-7..",
+        "L--J.L7...LJS7F-7L7.",
+        "....F-J..F7FJ|L7L7L7",
+        "....L7.F7||L7|.L7L7|",
+        ".....|FJLJ|FJ|F7|.LJ",
+        "....FJL-7.||.||||...",
+        "....L---J.LJ.LJLJ...",
+    ];
+
+    #[test]
+    fn test_example() {
+        let (grid, start_pos) = parse_grid(EXAMPLE.iter()).unwrap();
+        for mode in [Mode::FloodFill, Mode::Pick, Mode::CrossCheck] {
+            assert_eq!(count_enclosed(&grid, start_pos, mode), 8);
+        }
+    }
+}
@@ -2,9 +2,15 @@ use advent_of_code_2023::day12::*;
 use std::io::{self, BufRead};
 
 fn main() {
+    // With --flexible, group specs may use ranges (1-3), optional groups (2?) and wildcards (*).
+    let is_flexible = std::env::args().nth(1).as_deref() == Some("--flexible");
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.unwrap());
-    let result = day12(lines);
+    let result = if is_flexible {
+        day12_flexible(lines)
+    } else {
+        day12(lines)
+    };
     println!("{}", result);
 }
 
@@ -17,6 +23,17 @@ fn day12(lines: impl Iterator<Item = impl AsRef<str>>) -> Num {
         .sum()
 }
 
+fn day12_flexible(lines: impl Iterator<Item = impl AsRef<str>>) -> Num {
+    lines
+        .map(|line| {
+            let (fountains, specs) = parse_flexible_line(line.as_ref()).expect("Invalid line");
+            GroupAutomaton::compile(&specs)
+                .count_matches(&fountains)
+                .expect("Possibility count overflowed")
+        })
+        .sum()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -33,5 +50,7 @@ mod test {
         ];
         let actual = day12(example.iter());
         assert_eq!(actual, 21);
+        let actual_flexible = day12_flexible(example.iter());
+        assert_eq!(actual_flexible, 21);
     }
 }
@@ -1,31 +1,62 @@
 use advent_of_code_2023::day12::*;
+use advent_of_code_2023::parallel::{default_workers, par_map_stream};
 use std::io::{self, BufRead};
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut workers = default_workers();
+    let mut factor = 5;
+    let mut separator = Fountain::Unknown;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--workers" => {
+                workers = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--workers needs a number")
+            }
+            "--factor" => {
+                factor = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--factor needs a number")
+            }
+            "--separator" => {
+                separator = args
+                    .next()
+                    .and_then(|c| Fountain::parse(&c))
+                    .expect("--separator needs one of . # ?")
+            }
+            _ => panic!("Unknown argument {}", arg),
+        }
+    }
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.unwrap());
-    let result = day12b(lines);
-    println!("{}", result);
-}
-
-fn day12b(lines: impl Iterator<Item = impl AsRef<str>>) -> Num {
-    lines
-        .map(|line| {
-            let (mut fountains, mut groups) = parse_fountain_line(line.as_ref());
-            quintuple_inputs(&mut fountains, &mut groups);
-            get_possibility_count(&fountains, &groups)
-        })
-        .sum()
+    match day12b(lines, factor, separator, workers) {
+        Ok(result) => println!("{}", result),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
-fn quintuple_inputs(fountains: &mut Fountains, groups: &mut Groups) {
-    let mut fountains2 = fountains.clone();
-    for _ in 0..4 {
-        fountains2.push(Fountain::Unknown);
-        fountains2.extend(fountains.clone().into_iter());
-    }
-    *fountains = fountains2;
-    *groups = groups.repeat(5);
+fn day12b(
+    lines: impl Iterator<Item = impl AsRef<str>>,
+    factor: usize,
+    separator: Fountain,
+    workers: usize,
+) -> Result<Num, &'static str> {
+    let rows = lines.map(|line| parse_fountain_line(line.as_ref()));
+    par_map_stream(rows, workers, |(fountains, groups)| {
+        unfolded_possibility_count(&fountains, &groups, factor, separator)
+    })
+    .into_iter()
+    .try_fold(0 as Num, |total, row_count| {
+        total
+            .checked_add(row_count?)
+            .ok_or("Total count overflowed")
+    })
 }
 
 #[cfg(test)]
@@ -42,7 +73,11 @@ mod test {
             "????.######..#####. 1,6,5",
             "?###???????? 3,2,1",
         ];
-        let actual = day12b(example.iter());
-        assert_eq!(actual, 525152);
+        let actual = day12b(example.iter(), 5, Fountain::Unknown, 1);
+        assert_eq!(actual, Ok(525152));
+        let actual_parallel = day12b(example.iter(), 5, Fountain::Unknown, 4);
+        assert_eq!(actual_parallel, actual);
+        // Unfolding once is part A
+        assert_eq!(day12b(example.iter(), 1, Fountain::Unknown, 1), Ok(21));
     }
 }
@@ -1,9 +1,24 @@
-use advent_of_code_2023::day13::day13;
+use advent_of_code_2023::day13::*;
 use std::io::{self, BufRead};
 
 fn main() {
+    let explain = std::env::args().nth(1).as_deref() == Some("explain");
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.expect("IO Error"));
-    let result = day13(lines, 1);
+    let patterns = analyze_patterns(lines, 1).expect("Invalid pattern");
+    if explain {
+        for (idx, reflections) in patterns.iter().enumerate() {
+            for reflection in reflections.iter().filter(|r| r.error_count() == 1) {
+                println!(
+                    "pattern {}: {:?} line at {}, smudge {:?}",
+                    idx, reflection.axis, reflection.position, reflection.smudges[0]
+                );
+            }
+        }
+    }
+    let result: u32 = patterns
+        .iter()
+        .map(|reflections| pattern_score(reflections, 1))
+        .sum();
     println!("{}", result);
 }
@@ -2,14 +2,26 @@ use advent_of_code_2023::{cursor_grid::*, day16::*};
 use std::io::{self, BufRead};
 
 fn main() {
+    let components = components_from_args();
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.expect("IO Error"));
-    let result = day16(lines);
+    let result = day16(lines, &components);
     println!("{}", result);
 }
 
-fn day16(lines: impl Iterator<Item = impl AsRef<str>>) -> Num {
-    let grid = parse_grid(lines);
+/// Extra components given as `c=name` arguments, e.g. `#=absorber`.
+fn components_from_args() -> ComponentSet {
+    std::env::args()
+        .skip(1)
+        .fold(ComponentSet::standard(), |components, arg| {
+            components
+                .with_spec(&arg)
+                .expect("Arguments must be c=name")
+        })
+}
+
+fn day16(lines: impl Iterator<Item = impl AsRef<str>>, components: &ComponentSet) -> Num {
+    let grid = components.parse_grid(lines);
     let start_cursor = ((0, 0), Direction::Right);
     count_energized_tiles(start_cursor, &grid)
 }
@@ -1,31 +1,60 @@
-use advent_of_code_2023::{cursor_grid::*, day16::*};
+use advent_of_code_2023::{day16::*, parallel::default_workers};
 use std::io::{self, BufRead};
 
 fn main() {
+    let mut show_render = false;
+    let mut workers = default_workers();
+    let mut components = ComponentSet::standard();
+    // Extra components can be given as `c=name` arguments, e.g. `#=absorber`.
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "render" {
+            show_render = true;
+        } else if arg == "--workers" {
+            workers = args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .expect("--workers needs a number");
+        } else {
+            components = components
+                .with_spec(&arg)
+                .expect("Arguments must be render or c=name");
+        }
+    }
     let stdin = io::stdin();
     let lines = stdin.lock().lines().map(|l| l.expect("IO Error"));
-    let result = day16b(lines);
-    println!("{}", result);
+    let grid = components.parse_grid(lines);
+    let ranked = rank_entry_cursors(&grid, workers);
+    let (best_cursor, best_count) = ranked[0];
+    if show_render {
+        let trace = trace_beams(best_cursor, &grid);
+        println!("{}", render_energized(&grid, &trace.energized));
+        for (cursor, count) in ranked.iter().take(5) {
+            println!("{:?}: {}", cursor, count);
+        }
+    }
+    println!("{}", best_count);
 }
 
-fn day16b(lines: impl Iterator<Item = impl AsRef<str>>) -> Num {
-    let grid = parse_grid(lines);
-    let start_cursors = get_initial_cursors(&grid);
-    start_cursors
-        .map(|start_cursor| count_energized_tiles(start_cursor, &grid))
-        .max()
-        .unwrap()
-}
+#[cfg(test)]
+mod test {
+    use super::*;
 
-fn get_initial_cursors<T>(grid: &Grid<T>) -> impl Iterator<Item = Cursor> {
-    let h = grid.rows.len() as i32;
-    let w = grid.rows[0].len() as i32;
-    let left_cursors = (0..h).map(|y| ((y, 0), Direction::Right));
-    let right_cursors = (0..h).map(move |y| ((y, w - 1), Direction::Left));
-    let top_cursors = (0..w).map(|x| ((0, x), Direction::Down));
-    let bottom_cursors = (0..w as i32).map(move |x| ((h - 1, x), Direction::Up));
-    left_cursors
-        .chain(right_cursors)
-        .chain(top_cursors)
-        .chain(bottom_cursors)
+    #[test]
+    fn test_example() {
+        let example = [
+            r".|...\....",
+            r"|.-.\.....",
+            r".....|-...",
+            r"........|.",
+            r"..........",
+            r".........\",
+            r"..../.\\..",
+            r".-.-/..|..",
+            r".|....-|.\",
+            r"..//.|....",
+        ];
+        let grid = parse_grid(example.iter());
+        assert_eq!(rank_entry_cursors(&grid, 2)[0].1, 51);
+    }
 }
@@ -1,5 +1,6 @@
 use std::{collections::BTreeSet, ops::Add};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Grid<Node> {
     pub rows: Vec<Vec<Node>>,
 }
@@ -61,6 +62,20 @@ impl<Node> Grid<Node> {
             .map(|(neighbor_pos, _)| neighbor_pos)
     }
 
+    /// Cursors on every edge tile, facing into the grid.
+    pub fn edge_cursors(&self) -> impl Iterator<Item = Cursor> {
+        let h = self.rows.len() as i32;
+        let w = self.rows[0].len() as i32;
+        let left_cursors = (0..h).map(|y| ((y, 0), Right));
+        let right_cursors = (0..h).map(move |y| ((y, w - 1), Left));
+        let top_cursors = (0..w).map(|x| ((0, x), Down));
+        let bottom_cursors = (0..w).map(move |x| ((h - 1, x), Up));
+        left_cursors
+            .chain(right_cursors)
+            .chain(top_cursors)
+            .chain(bottom_cursors)
+    }
+
     pub fn paint_fill(&mut self, pos: Coord, new_color: Node)
     where
         Node: Clone + Eq,
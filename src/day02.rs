@@ -1,87 +1,453 @@
-use pest::Parser;
+use pest::error::{ErrorVariant, LineColLocation};
 use pest::iterators::Pair;
+use pest::Parser;
 use pest_derive::Parser;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    /// The input doesn't match the grammar. `expected` names the rules that could have matched at
+    /// that point.
+    Syntax {
+        line: usize,
+        column: usize,
+        expected: Vec<String>,
+    },
+    /// A draw names the same color twice.
+    DuplicateColor {
+        color: String,
+        line: usize,
+        column: usize,
+    },
+    /// A game number or cube count too large to store.
+    NumberTooLarge { line: usize, column: usize },
+}
+
+impl GameError {
+    fn from_pest(err: pest::error::Error<Rule>) -> Self {
+        let (line, column) = match err.line_col {
+            LineColLocation::Pos(pos) => pos,
+            LineColLocation::Span(start, _) => start,
+        };
+        let expected = match err.variant {
+            ErrorVariant::ParsingError { positives, .. } => {
+                positives.into_iter().map(describe_rule).collect()
+            }
+            ErrorVariant::CustomError { message } => vec![message],
+        };
+        GameError::Syntax {
+            line,
+            column,
+            expected,
+        }
+    }
+}
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct RGBCount {
-    pub red: u8,
-    pub green: u8,
-    pub blue: u8,
+/// Read a `num` pair, reporting where it was if it doesn't fit.
+fn parse_num(num: Pair<Rule>) -> Result<u32, GameError> {
+    num.as_str().parse::<u32>().map_err(|_| {
+        let (line, column) = num.line_col();
+        GameError::NumberTooLarge { line, column }
+    })
+}
+
+/// What a rule matches, in the words an error message would use.
+fn describe_rule(rule: Rule) -> String {
+    match rule {
+        Rule::game => "a game".to_string(),
+        Rule::drawlist | Rule::drawstats => "a draw".to_string(),
+        Rule::count => "a count".to_string(),
+        Rule::color => "a color".to_string(),
+        Rule::num => "a number".to_string(),
+        Rule::EOI => "end of input".to_string(),
+        rule => format!("{:?}", rule),
+    }
+}
+
+/// The colors of the original puzzle.
+pub const RGB_COLORS: [&str; 3] = ["red", "green", "blue"];
+
+/// How many cubes of each color, in a draw or a bag. Colors that aren't listed count as zero.
+#[derive(Debug, Clone, Default, Eq)]
+pub struct ColorCount {
+    counts: BTreeMap<String, u32>,
+}
+
+impl PartialEq for ColorCount {
+    /// An explicit zero is the same as leaving the color out.
+    fn eq(&self, other: &Self) -> bool {
+        let listed = |counts: &Self| -> Vec<(String, u32)> {
+            counts
+                .iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(color, count)| (color.to_string(), count))
+                .collect()
+        };
+        listed(self) == listed(other)
+    }
 }
 
-impl RGBCount {
-    pub fn new(red: u8, green: u8, blue: u8) -> Self {
-        Self { red, green, blue }
+impl ColorCount {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn from_parse_rule(rgb_count: Pair<Rule>) -> Self {
-        let mut red = 0;
-        let mut green = 0;
-        let mut blue = 0;
-        for color_count in rgb_count.into_inner() {
+    pub fn rgb(red: u32, green: u32, blue: u32) -> Self {
+        Self::from_pairs([("red", red), ("green", green), ("blue", blue)])
+    }
+
+    pub fn from_pairs<S: Into<String>>(pairs: impl IntoIterator<Item = (S, u32)>) -> Self {
+        Self {
+            counts: pairs
+                .into_iter()
+                .map(|(color, count)| (color.into(), count))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, color: &str) -> u32 {
+        self.counts.get(color).copied().unwrap_or(0)
+    }
+
+    /// Sets the count for a color, returning the previous count if it was listed.
+    pub fn insert(&mut self, color: impl Into<String>, count: u32) -> Option<u32> {
+        self.counts.insert(color.into(), count)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32)> + '_ {
+        self.counts
+            .iter()
+            .map(|(color, count)| (color.as_str(), *count))
+    }
+
+    /// Parse a bag spec like `12 red, 13 green, 14 blue`.
+    pub fn parse(spec: &str) -> Result<Self, GameError> {
+        let color_counts = Day2Parser::parse(Rule::bag, spec)
+            .map_err(GameError::from_pest)?
+            .next()
+            .unwrap();
+        Self::from_parse_rule(color_counts)
+    }
+
+    fn from_parse_rule(color_counts: Pair<Rule>) -> Result<Self, GameError> {
+        let mut counts = Self::new();
+        for color_count in color_counts.into_inner() {
             match color_count.as_rule() {
                 Rule::count => {
-                    let mut num: u8 = 0;
-                    for count_or_color in color_count.into_inner() {
-                        match count_or_color.as_rule() {
-                            Rule::num => num = count_or_color.as_str().parse::<u8>().unwrap(),
-                            Rule::color => match count_or_color.as_str() {
-                                "red" => red = num,
-                                "green" => green = num,
-                                "blue" => blue = num,
-                                _ => panic!(),
-                            },
-                            _ => panic!(),
-                        }
+                    let (line, column) = color_count.as_span().start_pos().line_col();
+                    let mut inner = color_count.into_inner();
+                    let num = parse_num(inner.next().unwrap())?;
+                    let color = inner.next().unwrap().as_str();
+                    if counts.insert(color, num).is_some() {
+                        return Err(GameError::DuplicateColor {
+                            color: color.to_string(),
+                            line,
+                            column,
+                        });
                     }
-                },
+                }
                 _ => panic!(),
             }
         }
-        RGBCount { red, green, blue }
+        Ok(counts)
+    }
+
+    /// The product of the counts of the given colors, so any color missing here makes it zero.
+    pub fn power_over(&self, colors: &[&str]) -> u64 {
+        colors.iter().map(|color| self.get(color) as u64).product()
     }
 
-    pub fn power(self: &Self) -> u32 {
-        self.red as u32 * self.blue as u32 * self.green as u32
+    /// Whether these cubes could all have come out of `bag`.
+    pub fn fits_in(&self, bag: &ColorCount) -> bool {
+        self.iter().all(|(color, count)| count <= bag.get(color))
+    }
+
+    /// The larger count for each color listed in either.
+    pub fn max(&self, other: &ColorCount) -> ColorCount {
+        let mut result = self.clone();
+        for (color, count) in other.iter() {
+            let entry = result.counts.entry(color.to_string()).or_default();
+            *entry = (*entry).max(count);
+        }
+        result
     }
 }
 
 pub struct Game {
     pub num: u32,
-    pub draws: Vec<RGBCount>,
+    pub draws: Vec<ColorCount>,
+}
+
+/// Why a game couldn't have been played with some bag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Infeasibility {
+    /// The index of the first draw that needs more cubes than the bag has.
+    pub draw_idx: usize,
+    /// Each color that draw is short of, as (color, drawn, in the bag).
+    pub shortfalls: Vec<(String, u32, u32)>,
 }
 
 impl Game {
-    pub fn parse(line: &str) -> Self {
-        Self::from_parse_rule(Day2Parser::parse(Rule::game, line).expect("unsuccessful parse").next().unwrap())
+    pub fn parse(line: &str) -> Result<Self, GameError> {
+        let game = Day2Parser::parse(Rule::gameline, line)
+            .map_err(GameError::from_pest)?
+            .next()
+            .unwrap();
+        Self::from_parse_rule(game)
     }
 
-    fn from_parse_rule(game: Pair<Rule>) -> Self {
+    fn from_parse_rule(game: Pair<Rule>) -> Result<Self, GameError> {
         let mut num = 0;
-        let mut draws: Vec<RGBCount> = Vec::new();
+        let mut draws: Vec<ColorCount> = Vec::new();
 
         for rule in game.into_inner() {
             match rule.as_rule() {
                 Rule::num => {
-                    num = rule.as_str().parse::<u32>().unwrap();
-                },
+                    num = parse_num(rule)?;
+                }
                 Rule::drawlist => {
-                    for rgb_rule in rule.into_inner() {
-                        match rgb_rule.as_rule() {
-                            Rule::drawstats => draws.push(RGBCount::from_parse_rule(rgb_rule)),
+                    for draw_rule in rule.into_inner() {
+                        match draw_rule.as_rule() {
+                            Rule::drawstats => draws.push(ColorCount::from_parse_rule(draw_rule)?),
                             _ => panic!(),
                         }
                     }
-                },
+                }
                 _ => panic!(),
             }
         }
 
-        Game { num, draws }
+        Ok(Game { num, draws })
+    }
+
+    pub fn is_feasible(&self, bag: &ColorCount) -> bool {
+        self.draws.iter().all(|draw| draw.fits_in(bag))
+    }
+
+    /// The first draw that couldn't have come out of `bag`, if any.
+    pub fn infeasible_draw(&self, bag: &ColorCount) -> Option<Infeasibility> {
+        let draw_idx = self.draws.iter().position(|draw| !draw.fits_in(bag))?;
+        let shortfalls = self.draws[draw_idx]
+            .iter()
+            .filter(|(color, count)| *count > bag.get(color))
+            .map(|(color, count)| (color.to_string(), count, bag.get(color)))
+            .collect();
+        Some(Infeasibility {
+            draw_idx,
+            shortfalls,
+        })
+    }
+
+    /// The smallest bag every draw fits in.
+    pub fn min_bag(&self) -> ColorCount {
+        self.draws
+            .iter()
+            .fold(ColorCount::new(), |bag, draw| bag.max(draw))
     }
 }
 
+/// The smallest bag that every game could have been played with.
+pub fn min_bag_for_all(games: &[Game]) -> ColorCount {
+    games
+        .iter()
+        .fold(ColorCount::new(), |bag, game| bag.max(&game.min_bag()))
+}
+
+/// For each bag, the numbers of the games that could have been played with it.
+pub fn feasible_games(games: &[Game], bags: &[ColorCount]) -> Vec<Vec<u32>> {
+    bags.iter()
+        .map(|bag| {
+            games
+                .iter()
+                .filter(|game| game.is_feasible(bag))
+                .map(|game| game.num)
+                .collect()
+        })
+        .collect()
+}
+
+/// Parse a whole file of games, one per line.
+pub fn parse_games(input: &str) -> Result<Vec<Game>, GameError> {
+    let gamelist = Day2Parser::parse(Rule::gamelist, input)
+        .map_err(GameError::from_pest)?
+        .next()
+        .unwrap();
+    gamelist
+        .into_inner()
+        .filter(|rule| rule.as_rule() == Rule::game)
+        .map(Game::from_parse_rule)
+        .collect()
+}
 
 #[derive(Parser)]
 #[grammar = "day02.pest"]
 pub struct Day2Parser;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_any_colors() {
+        let game = Game::parse("Game 7: 2 teal, 1 red; 3 mauve").unwrap();
+        assert_eq!(game.num, 7);
+        assert_eq!(
+            game.draws,
+            vec![
+                ColorCount::from_pairs([("teal", 2), ("red", 1)]),
+                ColorCount::from_pairs([("mauve", 3)]),
+            ]
+        );
+        let bag = ColorCount::from_pairs([("teal", 2), ("red", 5), ("mauve", 3)]);
+        assert!(game.draws.iter().all(|draw| draw.fits_in(&bag)));
+        assert!(!game.draws[1].fits_in(&ColorCount::rgb(5, 5, 5)));
+        let min_bag = game.draws[0].max(&game.draws[1]);
+        assert_eq!(
+            min_bag,
+            ColorCount::from_pairs([("teal", 2), ("red", 1), ("mauve", 3)])
+        );
+        assert_eq!(min_bag.power_over(&["teal", "red", "mauve"]), 6);
+        assert_eq!(min_bag.power_over(&RGB_COLORS), 0);
+    }
+
+    #[test]
+    fn test_missing_color() {
+        let game = Game::parse("Game 1: 3 blue, 4 red; 1 red, 6 blue").unwrap();
+        assert_eq!(game.min_bag(), ColorCount::rgb(4, 0, 6));
+        assert_eq!(game.min_bag().power_over(&RGB_COLORS), 0);
+        assert_ne!(game.min_bag(), ColorCount::rgb(4, 1, 6));
+        assert_eq!(ColorCount::new(), ColorCount::from_pairs([("green", 0)]));
+    }
+
+    #[test]
+    fn test_duplicate_color() {
+        assert_eq!(
+            Game::parse("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 red").err(),
+            Some(GameError::DuplicateColor {
+                color: "red".to_string(),
+                line: 1,
+                column: 40,
+            })
+        );
+        // The same color in different draws is fine.
+        assert!(Game::parse("Game 1: 3 blue; 4 blue").is_ok());
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        assert!(matches!(
+            Game::parse("Game 1: 3 blue 4 red"),
+            Err(GameError::Syntax { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_games() {
+        let input = "Game 1: 3 blue, 4 red\nGame 2: 1 green; 2 teal\n";
+        let games = parse_games(input).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].num, 2);
+        assert_eq!(games[1].draws[1], ColorCount::from_pairs([("teal", 2)]));
+        assert_eq!(parse_games("Game 1: 3 blue").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_games_errors() {
+        assert_eq!(
+            parse_games("Game 1: 3 blue\nGame 2: x red\n").err(),
+            Some(GameError::Syntax {
+                line: 2,
+                column: 9,
+                expected: vec!["a number".to_string()],
+            })
+        );
+        assert_eq!(
+            parse_games("Game 1: 3 blue\nGame 2 1 red").err(),
+            Some(GameError::Syntax {
+                line: 2,
+                column: 1,
+                expected: vec!["end of input".to_string(), "a game".to_string()],
+            })
+        );
+        assert_eq!(
+            Game::parse("Game 1: 3 blue 4 red").err(),
+            Some(GameError::Syntax {
+                line: 1,
+                column: 15,
+                expected: vec!["end of input".to_string()],
+            })
+        );
+        assert_eq!(
+            parse_games("Game 1: 99999999999 blue").err(),
+            Some(GameError::NumberTooLarge { line: 1, column: 9 })
+        );
+        assert_eq!(
+            parse_games("Game 1: 3 blue\nGame 99999999999: 1 red").err(),
+            Some(GameError::NumberTooLarge { line: 2, column: 6 })
+        );
+        assert_eq!(
+            parse_games("Game 1: 3 blue\nGame 2: 1 red, 2 red").err(),
+            Some(GameError::DuplicateColor {
+                color: "red".to_string(),
+                line: 2,
+                column: 16,
+            })
+        );
+    }
+
+    const EXAMPLE: &str = "\
+Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+";
+
+    #[test]
+    fn test_bag_queries() {
+        let games = parse_games(EXAMPLE).unwrap();
+        let bags = [
+            ColorCount::parse("12 red, 13 green, 14 blue").unwrap(),
+            ColorCount::parse("20 red, 13 green, 15 blue").unwrap(),
+            ColorCount::parse("1 red").unwrap(),
+        ];
+        assert_eq!(
+            feasible_games(&games, &bags),
+            vec![vec![1, 2, 5], vec![1, 2, 3, 4, 5], vec![]]
+        );
+        assert_eq!(
+            games[2].infeasible_draw(&bags[0]),
+            Some(Infeasibility {
+                draw_idx: 0,
+                shortfalls: vec![("red".to_string(), 20, 12)],
+            })
+        );
+        assert_eq!(
+            games[3].infeasible_draw(&bags[0]),
+            Some(Infeasibility {
+                draw_idx: 2,
+                shortfalls: vec![("blue".to_string(), 15, 14), ("red".to_string(), 14, 12)],
+            })
+        );
+        assert_eq!(games[0].infeasible_draw(&bags[0]), None);
+        assert_eq!(games[2].min_bag(), ColorCount::rgb(20, 13, 6));
+        assert_eq!(min_bag_for_all(&games), ColorCount::rgb(20, 13, 15));
+        assert!(games
+            .iter()
+            .all(|game| game.is_feasible(&min_bag_for_all(&games))));
+    }
+
+    #[test]
+    fn test_parse_bag() {
+        assert_eq!(
+            ColorCount::parse("2 teal, 1 red").unwrap(),
+            ColorCount::from_pairs([("teal", 2), ("red", 1)])
+        );
+        assert!(ColorCount::parse("2 teal, 1 teal").is_err());
+        assert!(ColorCount::parse("2 teal,").is_err());
+        assert_eq!(
+            ColorCount::parse("2 teal, 99999999999 red"),
+            Err(GameError::NumberTooLarge { line: 1, column: 9 })
+        );
+    }
+}
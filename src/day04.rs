@@ -1,5 +1,4 @@
-use regex::Regex;
-use std::collections::{HashMap, HashSet};
+type NumSet = u128;
 
 pub fn day04(lines: impl Iterator<Item = impl AsRef<str>>) -> u32 {
     let mut sum = 0;
@@ -14,70 +13,141 @@ fn score_for_line(line: &str) -> u32 {
     card.get_score()
 }
 
-pub fn day04b(lines: impl Iterator<Item = impl AsRef<str>>) -> u32 {
-    let mut card_counts: HashMap<u32, u32> = HashMap::new();
-    for card in lines.map(|line| Card::from_line(line.as_ref())) {
-        let curr_card_count = card_counts.entry(card.id).or_insert(0);
-        *curr_card_count += 1;
-        let curr_card_count_ = *curr_card_count;
-        let matches = card.get_matches() as u32;
-        for other_card_id_offset in 1..matches + 1 {
-            let other_card_id: u32 = card.id + other_card_id_offset;
-            let other_card_count = card_counts.entry(other_card_id).or_insert(0);
-            *other_card_count += curr_card_count_;
+pub fn day04b(lines: impl Iterator<Item = impl AsRef<str>>) -> Result<u32, &'static str> {
+    let cards: Vec<Card> = lines.map(|line| Card::from_line(line.as_ref())).collect();
+    Ok(cascade_counts(&cards)?.iter().sum())
+}
+
+/// How many copies of each card you end up with, originals included, in the same order as `cards`.
+/// Cards must be listed in order with contiguous ids. Copies of cards past the end of the table
+/// are never won.
+pub fn cascade_counts(cards: &[Card]) -> Result<Vec<u32>, &'static str> {
+    let first_id = cards.first().map_or(0, |card| card.id);
+    for (idx, card) in cards.iter().enumerate() {
+        if card.id != first_id + idx as u32 {
+            return Err("Card ids must be contiguous and in order");
+        }
+    }
+    let mut card_counts = vec![1; cards.len()];
+    for idx in 0..cards.len() {
+        for won_idx in won_range(cards, idx) {
+            card_counts[won_idx] += card_counts[idx];
+        }
+    }
+    Ok(card_counts)
+}
+
+/// Where one card's copies came from, and how many copies it handed out in turn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardCopies {
+    pub id: u32,
+    /// Copies won, not counting the original.
+    pub won: u32,
+    /// Each earlier card that won copies of this one, with how many it won.
+    pub won_from: Vec<(u32, u32)>,
+    /// Copies of later cards won by all the copies of this card together.
+    pub generated: u32,
+}
+
+pub fn copy_provenance(cards: &[Card]) -> Result<Vec<CardCopies>, &'static str> {
+    let card_counts = cascade_counts(cards)?;
+    let mut provenance: Vec<CardCopies> = cards
+        .iter()
+        .zip(&card_counts)
+        .map(|(card, count)| CardCopies {
+            id: card.id,
+            won: count - 1,
+            won_from: Vec::new(),
+            generated: 0,
+        })
+        .collect();
+    for (idx, card) in cards.iter().enumerate() {
+        for won_idx in won_range(cards, idx) {
+            provenance[won_idx]
+                .won_from
+                .push((card.id, card_counts[idx]));
+            provenance[idx].generated += card_counts[idx];
         }
     }
-    let grand_total = card_counts.values().sum();
-    return grand_total;
+    Ok(provenance)
+}
+
+/// Draw every copy of every card as a tree, with the copies each one wins as its children.
+/// There's a line per card, so this refuses to draw more than `max_lines`.
+pub fn render_cascade_tree(cards: &[Card], max_lines: u32) -> Result<String, &'static str> {
+    let total: u32 = cascade_counts(cards)?.iter().sum();
+    if total > max_lines {
+        return Err("Too many cards to draw");
+    }
+    let mut lines = Vec::new();
+    for idx in 0..cards.len() {
+        lines.push(format!("Card {}", cards[idx].id));
+        render_subtree(cards, idx, "", &mut lines);
+    }
+    Ok(lines.join("\n"))
+}
+
+fn render_subtree(cards: &[Card], idx: usize, prefix: &str, lines: &mut Vec<String>) {
+    let won = won_range(cards, idx);
+    let last_idx = won.end.saturating_sub(1);
+    for won_idx in won {
+        let (branch, indent) = match won_idx == last_idx {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   "),
+        };
+        lines.push(format!("{}{}Card {}", prefix, branch, cards[won_idx].id));
+        render_subtree(cards, won_idx, &format!("{}{}", prefix, indent), lines);
+    }
+}
+
+/// The indexes of the cards that each copy of `cards[idx]` wins a copy of.
+fn won_range(cards: &[Card], idx: usize) -> std::ops::Range<usize> {
+    let matches = cards[idx].get_matches() as usize;
+    idx + 1..(idx + 1 + matches).min(cards.len())
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Scratchcard numbers, stored as bitsets so matching is a single AND.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Card {
     id: u32,
-    win_nums: HashSet<u8>,
-    your_nums: HashSet<u8>,
+    win_nums: NumSet,
+    your_nums: NumSet,
 }
 
 impl Card {
-    pub fn new(id: u32, win_nums: HashSet<u8>, your_nums: HashSet<u8>) -> Self {
+    pub fn new(
+        id: u32,
+        win_nums: impl IntoIterator<Item = u8>,
+        your_nums: impl IntoIterator<Item = u8>,
+    ) -> Self {
         Self {
             id,
-            win_nums,
-            your_nums,
+            win_nums: to_num_set(win_nums),
+            your_nums: to_num_set(your_nums),
         }
     }
 
     pub fn from_line(line: &str) -> Self {
-        let id: u32;
-        let mut win_nums: HashSet<u8> = HashSet::new();
-        let mut your_nums: HashSet<u8> = HashSet::new();
-        let whole_re =
-            Regex::new(r"Card\s+([0-9]+):(?:\s+([0-9]+))*\s*(\|)(?:\s+([0-9]+))*").unwrap();
-        //let capture = whole_re.captures_iter(&line).next().unwrap();
-        //let mut subcaptures = capture.iter().map(|m| m.unwrap().as_str()).skip(1);
-        whole_re
-            .captures_iter(&line)
-            .next()
-            .expect("Invalid Card string");
-        let re = Regex::new(r"([0-9]+|\|)").unwrap();
-        let mut captures = re.find_iter(&line).map(|m| m.as_str());
-        id = captures.next().unwrap().parse().unwrap();
-        for find in &mut captures {
-            if "|" == find {
-                break;
-            }
-            let num: u8 = find.parse().unwrap();
-            win_nums.insert(num);
-        }
-        for find in &mut captures {
-            let num: u8 = find.parse().unwrap();
-            your_nums.insert(num);
-        }
-        Self::new(id, win_nums, your_nums)
+        let (label, numbers) = line.split_once(':').expect("Invalid Card string");
+        let id = label
+            .strip_prefix("Card")
+            .and_then(|id| id.trim().parse().ok())
+            .expect("Invalid Card id");
+        let (win_nums, your_nums) = numbers.split_once('|').expect("Invalid Card string");
+        let parse_nums = |nums: &str| -> Vec<u8> {
+            nums.split_whitespace()
+                .map(|num| num.parse().expect("Invalid Card number"))
+                .collect()
+        };
+        Self::new(id, parse_nums(win_nums), parse_nums(your_nums))
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
     }
 
     pub fn get_matches(self: &Self) -> u8 {
-        self.win_nums.intersection(&self.your_nums).count() as u8
+        (self.win_nums & self.your_nums).count_ones() as u8
     }
 
     pub fn get_score(self: &Self) -> u32 {
@@ -88,20 +158,22 @@ impl Card {
     }
 }
 
+fn to_num_set(nums: impl IntoIterator<Item = u8>) -> NumSet {
+    nums.into_iter().fold(0, |set, num| {
+        assert!(num < NumSet::BITS as u8, "Card numbers must be below 128");
+        set | 1 << num
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::collections::HashSet;
 
     #[test]
     fn test_parsing() {
         let line: &str = "Card 1: 2 30 4 | 5 6 7";
         let actual = Card::from_line(&line);
-        let expected = Card::new(
-            1,
-            HashSet::<u8>::from([2, 30, 4]),
-            HashSet::<u8>::from([5, 6, 7]),
-        );
+        let expected = Card::new(1, [2, 30, 4], [5, 6, 7]);
         assert_eq!(&expected, &actual);
     }
 
@@ -163,6 +235,68 @@ mod test {
             "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11",
         ];
         let result = day04b(&mut lines.into_iter());
-        assert_eq!(result, 30);
+        assert_eq!(result, Ok(30));
+    }
+
+    #[test]
+    fn test_cascade_needs_contiguous_ids() {
+        let cards = [
+            Card::from_line("Card 1: 1 2 | 1 2"),
+            Card::from_line("Card 2: 1 | 1"),
+            Card::from_line("Card 3: 1 | 2"),
+        ];
+        assert_eq!(cascade_counts(&cards), Ok(vec![1, 2, 4]));
+        assert_eq!(cascade_counts(&cards[1..]), Ok(vec![1, 2]));
+        assert!(cascade_counts(&[cards[0].clone(), cards[2].clone()]).is_err());
+        assert!(cascade_counts(&[cards[1].clone(), cards[0].clone()]).is_err());
+        assert_eq!(cascade_counts(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn example_provenance() {
+        let cards: Vec<Card> = [
+            "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53",
+            "Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19",
+            "Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1",
+            "Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83",
+            "Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36",
+            "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11",
+        ]
+        .iter()
+        .map(|line| Card::from_line(line))
+        .collect();
+        let provenance = copy_provenance(&cards).unwrap();
+        assert_eq!(
+            provenance[4],
+            CardCopies {
+                id: 5,
+                won: 13,
+                won_from: vec![(1, 1), (3, 4), (4, 8)],
+                generated: 0,
+            }
+        );
+        let generated: Vec<u32> = provenance.iter().map(|c| c.generated).collect();
+        assert_eq!(generated, vec![4, 4, 8, 8, 0, 0]);
+        assert_eq!(provenance[0].won_from, vec![]);
+    }
+
+    #[test]
+    fn test_cascade_tree() {
+        let cards = [
+            Card::from_line("Card 1: 1 2 | 1 2"),
+            Card::from_line("Card 2: 1 | 1"),
+            Card::from_line("Card 3: 1 | 2"),
+        ];
+        let expected = [
+            "Card 1",
+            "├── Card 2",
+            "│   └── Card 3",
+            "└── Card 3",
+            "Card 2",
+            "└── Card 3",
+            "Card 3",
+        ];
+        assert_eq!(render_cascade_tree(&cards, 7), Ok(expected.join("\n")));
+        assert!(render_cascade_tree(&cards, 6).is_err());
     }
 }
@@ -1,10 +1,12 @@
-use std::cmp::{max, Ordering};
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::fmt;
 
 use Card::*;
 use HandType::*;
 
+type Num = u32;
+
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Copy, Clone)]
 pub enum Card {
     Joker,
@@ -17,12 +19,13 @@ pub enum Card {
     Eight,
     Nine,
     Ten,
-    //Jack,
+    Jack,
     Queen,
     King,
     Ace,
 }
 
+/// The named types of five-card hands.
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Copy, Clone)]
 pub enum HandType {
     HighCard,
@@ -35,22 +38,22 @@ pub enum HandType {
 }
 
 impl Card {
-    fn from_str(card: &str) -> Option<Self> {
+    /// The card a symbol stands for in a standard deck. Jokers only come from a ruleset's wildcard.
+    fn from_char(card: char) -> Option<Self> {
         match card {
-            "A" => Some(Ace),
-            "K" => Some(King),
-            "Q" => Some(Queen),
-            //"J" => Some(Jack),
-            "T" => Some(Ten),
-            "9" => Some(Nine),
-            "8" => Some(Eight),
-            "7" => Some(Seven),
-            "6" => Some(Six),
-            "5" => Some(Five),
-            "4" => Some(Four),
-            "3" => Some(Three),
-            "2" => Some(Two),
-            "J" => Some(Joker),
+            'A' => Some(Ace),
+            'K' => Some(King),
+            'Q' => Some(Queen),
+            'J' => Some(Jack),
+            'T' => Some(Ten),
+            '9' => Some(Nine),
+            '8' => Some(Eight),
+            '7' => Some(Seven),
+            '6' => Some(Six),
+            '5' => Some(Five),
+            '4' => Some(Four),
+            '3' => Some(Three),
+            '2' => Some(Two),
             _ => None,
         }
     }
@@ -60,7 +63,7 @@ impl Card {
             Ace => "A",
             King => "K",
             Queen => "Q",
-            //Jack => "J",
+            Jack => "J",
             Ten => "T",
             Nine => "9",
             Eight => "8",
@@ -70,7 +73,7 @@ impl Card {
             Four => "4",
             Three => "3",
             Two => "2",
-            Joker => "J",
+            Joker => "*",
         }
     }
 }
@@ -81,97 +84,236 @@ impl fmt::Display for Card {
     }
 }
 
+/// Which symbol (if any) is wild, and how the cards rank when breaking ties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ruleset {
+    /// Card symbols from weakest to strongest.
+    ranking: Vec<char>,
+    /// The symbol dealt as a `Joker`, standing in for whichever card makes the best hand.
+    wildcard: Option<char>,
+}
+
+impl Ruleset {
+    pub fn new(ranking: &str, wildcard: Option<char>) -> Result<Self, &'static str> {
+        let ranking: Vec<char> = ranking.chars().collect();
+        for (idx, symbol) in ranking.iter().enumerate() {
+            if ranking[..idx].contains(symbol) {
+                return Err("Card symbols must only be ranked once");
+            }
+            if Some(*symbol) != wildcard && Card::from_char(*symbol).is_none() {
+                return Err("Unknown card symbol in ranking");
+            }
+        }
+        if let Some(wildcard) = wildcard {
+            if !ranking.contains(&wildcard) {
+                return Err("Wildcard symbol must be ranked");
+            }
+        }
+        Ok(Self { ranking, wildcard })
+    }
+
+    /// Part A: jacks are jacks.
+    pub fn standard() -> Self {
+        Self::new("23456789TJQKA", None).unwrap()
+    }
+
+    /// Part B: jacks become jokers, which are wild but the weakest card in a tie.
+    pub fn joker_wild() -> Self {
+        Self::new("J23456789TQKA", Some('J')).unwrap()
+    }
+
+    /// The card for a symbol along with its rank, if the symbol is in play.
+    fn parse_card(&self, symbol: char) -> Option<(Card, u8)> {
+        let rank = self.ranking.iter().position(|s| *s == symbol)? as u8;
+        if Some(symbol) == self.wildcard {
+            Some((Joker, rank))
+        } else {
+            Card::from_char(symbol).map(|card| (card, rank))
+        }
+    }
+}
+
+/// A hand's type, as the size of each group of matching cards, largest first.
+/// Signatures compare like the hands do: a bigger first group wins, then a bigger second group, and so on.
+#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Clone, Hash)]
+pub struct Signature(Vec<u8>);
+
+impl Signature {
+    pub fn from_counts(counts: impl IntoIterator<Item = u8>) -> Self {
+        let mut counts: Vec<u8> = counts.into_iter().filter(|count| *count > 0).collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        Self(counts)
+    }
+
+    pub fn counts(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The name for this shape of hand, if it's a five-card hand.
+    pub fn hand_type(&self) -> Option<HandType> {
+        match self.0.as_slice() {
+            [5] => Some(FiveKind),
+            [4, 1] => Some(FourKind),
+            [3, 2] => Some(FullHouse),
+            [3, 1, 1] => Some(ThreeKind),
+            [2, 2, 1] => Some(TwoPair),
+            [2, 1, 1, 1] => Some(OnePair),
+            [1, 1, 1, 1, 1] => Some(HighCard),
+            _ => None,
+        }
+    }
+
+    /// The hand type's name for five-card hands, or just the group sizes otherwise.
+    pub fn name(&self) -> String {
+        match self.hand_type() {
+            Some(hand_type) => format!("{:?}", hand_type),
+            None => self.sizes(),
+        }
+    }
+
+    fn sizes(&self) -> String {
+        let counts: Vec<String> = self.0.iter().map(|count| count.to_string()).collect();
+        counts.join("+")
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.sizes())?;
+        if let Some(hand_type) = self.hand_type() {
+            write!(f, " ({:?})", hand_type)?;
+        }
+        Ok(())
+    }
+}
+
+/// How a hand got its type.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Explanation {
+    pub signature: Signature,
+    /// The card every joker stands in for, if there are any jokers.
+    pub substitute: Option<Card>,
+    /// The hand with the jokers replaced by their substitute.
+    pub effective_cards: Vec<Card>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.signature)?;
+        if let Some(substitute) = self.substitute {
+            let effective: String = self.effective_cards.iter().map(Card::to_str).collect();
+            write!(f, " with jokers as {} ({})", substitute, effective)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Hand {
-    cards: [Card; 5],
+    cards: Vec<Card>,
+    symbols: String,
+    signature: Signature,
+    substitute: Option<Card>,
+    /// The signature then the rank of each card, four bits apiece and padded out to
+    /// `MAX_HAND_SIZE` entries each, so hands sort as integers.
+    sort_key: u128,
 }
 
 impl Hand {
-    pub fn from_str(line: &str) -> Option<Self> {
-        if line.len() != 5 {
-            return None;
-        }
-        let mut cards: [Option<Card>; 5] = [None; 5];
-        for (idx, char) in line.chars().enumerate().take(5) {
-            cards[idx] = Card::from_str(&char.to_string());
-        }
-        if !cards.iter().all(Option::is_some) {
+    /// Every group size and card rank has to fit in four bits of the sort key.
+    pub const MAX_HAND_SIZE: usize = 15;
+
+    pub fn from_str(line: &str, ruleset: &Ruleset) -> Option<Self> {
+        let (cards, ranks): (Vec<Card>, Vec<u8>) = line
+            .chars()
+            .map(|symbol| ruleset.parse_card(symbol))
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        if cards.is_empty() || cards.len() > Self::MAX_HAND_SIZE {
             return None;
         }
+        let (signature, substitute) = classify(&cards);
+        let pack = |values: &[u8]| {
+            (0..Self::MAX_HAND_SIZE).fold(0u128, |key, idx| {
+                key << 4 | *values.get(idx).unwrap_or(&0) as u128
+            })
+        };
+        let sort_key = pack(signature.counts()) << (4 * Self::MAX_HAND_SIZE) | pack(&ranks);
         Some(Self {
-            cards: cards.map(Option::unwrap),
+            cards,
+            symbols: line.to_string(),
+            signature,
+            substitute,
+            sort_key,
         })
     }
 
-    pub fn get_card_counts(self: &Self) -> BTreeMap<Card, u8> {
-        let mut card_counts = BTreeMap::new();
-        for card_type in [
-            Ace, King, Queen, Joker, Ten, Nine, Eight, Seven, Six, Five, Four, Three,
-            Two, // Jack
-        ] {
-            let count = self.cards.iter().filter(|c| *c == &card_type).count() as u8;
-            if count > 0 {
-                //println!("count {} {}", card_type, count);
-                *card_counts.entry(card_type).or_default() = count;
-            }
-        }
-        return card_counts;
-    }
-
-    pub fn get_type(self: &Self) -> HandType {
-        let card_counts = self.get_card_counts();
-        let mut most_numerous_card: Option<(u8, Card)> = None;
-        let mut second_most_numerous_card: Option<(u8, Card)> = None;
-        //println!("{:#?} {:#?}", &self, card_counts);
-        for (card, count) in card_counts.iter().rev() {
-            if let Some((curr_count, curr_card)) = most_numerous_card {
-                if (count, card) > (&curr_count, &curr_card) {
-                    second_most_numerous_card = most_numerous_card;
-                    most_numerous_card = Some((*count, *card));
-                } else if let Some((curr_count, curr_card)) = second_most_numerous_card {
-                    if (count, card) > (&curr_count, &curr_card) {
-                        second_most_numerous_card = Some((*count, *card));
-                    }
-                } else {
-                    second_most_numerous_card = Some((*count, *card));
-                }
-            } else {
-                most_numerous_card = Some((*count, *card));
-            }
-        }
-        // Joker boosting
-        let num_jokers = *card_counts.get(&Joker).unwrap_or(&0);
-        if let Some((foo, Joker)) = most_numerous_card {
-            most_numerous_card = Some(second_most_numerous_card.unwrap_or((0, Joker)));
-            // Second card type doesn't matter once jokers are in play (it's actually not needed at all at this point for this problem)
-            // that is, we will never choose a full-house or two pair when we can choose 4kind or 3kind respectivly
-            second_most_numerous_card = Some((1, Joker));
-        }
-        most_numerous_card =
-            most_numerous_card.map(|(count, card_type)| (count + num_jokers, card_type));
-
-        match most_numerous_card.unwrap() {
-            (5, _) => FiveKind,
-            (4, _) => FourKind,
-            (3, _) => match second_most_numerous_card.unwrap() {
-                (2, _) => FullHouse,
-                (1, _) => ThreeKind,
-                _ => unreachable!(),
-            },
-            (2, _) => match second_most_numerous_card.unwrap() {
-                (2, _) => TwoPair,
-                (1, _) => OnePair,
-                _ => unreachable!(),
-            },
-            (1, _) => HighCard,
-            _ => unreachable!(),
+    /// The named type of a five-card hand.
+    pub fn get_type(&self) -> Option<HandType> {
+        self.signature.hand_type()
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    pub fn sort_key(&self) -> u128 {
+        self.sort_key
+    }
+
+    pub fn get_card_counts(&self) -> BTreeMap<Card, u8> {
+        get_card_counts(&self.cards)
+    }
+
+    pub fn explain(&self) -> Explanation {
+        let effective_cards = self
+            .cards
+            .iter()
+            .map(|card| match (card, self.substitute) {
+                (Joker, Some(substitute)) => substitute,
+                _ => *card,
+            })
+            .collect();
+        Explanation {
+            signature: self.signature.clone(),
+            substitute: self.substitute,
+            effective_cards,
         }
     }
 }
 
+fn get_card_counts(cards: &[Card]) -> BTreeMap<Card, u8> {
+    let mut card_counts = BTreeMap::new();
+    for card in cards {
+        *card_counts.entry(*card).or_default() += 1;
+    }
+    card_counts
+}
+
+/// The best signature for the cards, and the card the jokers should all stand in for to get it.
+///
+/// Adding every joker to the largest group always wins: it makes the first group as big as it can
+/// be, and leaves the rest of the groups as they were. Ties go to the higher card.
+fn classify(cards: &[Card]) -> (Signature, Option<Card>) {
+    let mut card_counts = get_card_counts(cards);
+    let Some(num_jokers) = card_counts.remove(&Joker) else {
+        return (Signature::from_counts(card_counts.into_values()), None);
+    };
+    let substitute = card_counts
+        .iter()
+        .max_by_key(|(card, count)| (**count, **card))
+        .map_or(Ace, |(card, _)| *card);
+    *card_counts.entry(substitute).or_default() += num_jokers;
+    (
+        Signature::from_counts(card_counts.into_values()),
+        Some(substitute),
+    )
+}
+
 impl Ord for Hand {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.get_type(), self.cards).cmp(&(other.get_type(), other.cards))
+        self.sort_key.cmp(&other.sort_key)
     }
 }
 
@@ -189,6 +331,361 @@ impl PartialOrd for Hand {
 
 impl fmt::Display for Hand {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.cards.map(|ref c| c.to_str()).join(""))
+        write!(f, "{}", self.symbols)
+    }
+}
+
+/// Parse `<hand> <bid>` lines and sort them from weakest to strongest hand.
+pub fn rank_hands(
+    lines: impl Iterator<Item = impl AsRef<str>>,
+    ruleset: &Ruleset,
+) -> Vec<(Hand, Num)> {
+    let mut hands: Vec<(Hand, Num)> = lines
+        .map(|line| {
+            let mut split_line = line.as_ref().split_whitespace();
+            let hand = Hand::from_str(split_line.next().expect("No hand"), ruleset)
+                .expect("Failed to parse hand");
+            let bid = split_line
+                .next()
+                .expect("No bid")
+                .parse::<Num>()
+                .expect("Failed to parse bid");
+            (hand, bid)
+        })
+        .collect();
+    hands.sort_by_key(|(hand, _)| hand.sort_key());
+    hands
+}
+
+/// Each bid multiplied by its hand's rank, where the weakest hand is rank 1.
+pub fn total_winnings(ranked_hands: &[(Hand, Num)]) -> Num {
+    ranked_hands
+        .iter()
+        .enumerate()
+        .map(|(idx, (_, bid))| -> Num { (idx as Num + 1) * bid })
+        .sum()
+}
+
+pub fn day07(lines: impl Iterator<Item = impl AsRef<str>>, ruleset: &Ruleset) -> Num {
+    total_winnings(&rank_hands(lines, ruleset))
+}
+
+/// One row of the final standings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    /// 1 for the weakest hand.
+    pub rank: Num,
+    pub hand: String,
+    pub hand_type: String,
+    pub bid: Num,
+    pub winnings: Num,
+}
+
+pub fn standings(ranked_hands: &[(Hand, Num)]) -> Vec<Standing> {
+    ranked_hands
+        .iter()
+        .enumerate()
+        .map(|(idx, (hand, bid))| {
+            let rank = idx as Num + 1;
+            Standing {
+                rank,
+                hand: hand.to_string(),
+                hand_type: hand.signature().name(),
+                bid: *bid,
+                winnings: rank * bid,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TableFormat {
+    /// Comma-separated, with fields quoted as in RFC 4180 when they need it.
+    Csv,
+    /// Space-padded columns, numbers aligned to the right.
+    Text,
+}
+
+pub fn render_standings(standings: &[Standing], format: TableFormat) -> String {
+    let header = ["rank", "hand", "type", "bid", "winnings"].map(String::from);
+    let rows: Vec<[String; 5]> = standings
+        .iter()
+        .map(|standing| {
+            [
+                standing.rank.to_string(),
+                standing.hand.clone(),
+                standing.hand_type.clone(),
+                standing.bid.to_string(),
+                standing.winnings.to_string(),
+            ]
+        })
+        .collect();
+    let lines: Vec<String> = match format {
+        TableFormat::Csv => std::iter::once(&header)
+            .chain(rows.iter())
+            .map(|row| {
+                let cells: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+                cells.join(",")
+            })
+            .collect(),
+        TableFormat::Text => {
+            let widths: Vec<usize> = (0..header.len())
+                .map(|col| {
+                    std::iter::once(&header)
+                        .chain(rows.iter())
+                        .map(|row| row[col].len())
+                        .max()
+                        .unwrap()
+                })
+                .collect();
+            let is_numeric = [true, false, false, true, true];
+            std::iter::once(&header)
+                .chain(rows.iter())
+                .map(|row| {
+                    let cells: Vec<String> = row
+                        .iter()
+                        .enumerate()
+                        .map(|(col, cell)| match is_numeric[col] {
+                            true => format!("{:>width$}", cell, width = widths[col]),
+                            false => format!("{:<width$}", cell, width = widths[col]),
+                        })
+                        .collect();
+                    cells.join("  ").trim_end().to_string()
+                })
+                .collect()
+        }
+    };
+    lines.join("\n")
+}
+
+/// Quote a field if it holds a comma, quote or line break, doubling any quotes inside.
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lcg::lcg;
+    use std::time::Instant;
+
+    const EXAMPLE: &[&str] = &[
+        "32T3K 765",
+        "T55J5 684",
+        "KK677 28",
+        "KTJJT 220",
+        "QQQJA 483",
+    ];
+
+    #[test]
+    fn test_example() {
+        assert_eq!(day07(EXAMPLE.iter(), &Ruleset::standard()), 6440);
+    }
+
+    #[test]
+    fn test_example_b() {
+        assert_eq!(day07(EXAMPLE.iter(), &Ruleset::joker_wild()), 5905);
+    }
+
+    #[test]
+    fn test_rulesets() {
+        let standard = Ruleset::standard();
+        let joker_wild = Ruleset::joker_wild();
+        let hand = Hand::from_str("KTJJT", &standard).unwrap();
+        assert_eq!(hand.get_type(), Some(TwoPair));
+        let hand = Hand::from_str("KTJJT", &joker_wild).unwrap();
+        assert_eq!(hand.get_type(), Some(FourKind));
+        assert_eq!(hand.to_string(), "KTJJT");
+
+        // Jokers lose ties to every other card.
+        let jacks = Hand::from_str("JKKKK", &standard).unwrap();
+        let twos = Hand::from_str("2KKKK", &standard).unwrap();
+        assert!(twos < jacks);
+        let jokers = Hand::from_str("J2222", &joker_wild).unwrap();
+        let twos = Hand::from_str("22222", &joker_wild).unwrap();
+        assert!(jokers < twos);
+
+        let deuces_wild = Ruleset::new("23456789TJQKA", Some('2')).unwrap();
+        let hand = Hand::from_str("2J2A2", &deuces_wild).unwrap();
+        assert_eq!(hand.get_type(), Some(FourKind));
+        let deuces = Hand::from_str("22223", &deuces_wild).unwrap();
+        assert!(deuces < Hand::from_str("33332", &deuces_wild).unwrap());
+
+        assert!(Hand::from_str("", &standard).is_none());
+        assert!(Hand::from_str(&"A".repeat(16), &standard).is_none());
+        assert!(Hand::from_str("AAJAX", &standard).is_none());
+        assert!(Ruleset::new("23456789TJQKAA", None).is_err());
+        assert!(Ruleset::new("23456789TJQKA", Some('*')).is_err());
+        assert!(Ruleset::new("*23456789TJQKA", Some('*')).is_ok());
+    }
+
+    /// Run with `cargo test --release bench_sort -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_sort_million_hands() {
+        let ruleset = Ruleset::joker_wild();
+        let symbols: Vec<char> = "23456789TJQKA".chars().collect();
+        let mut next = lcg(7);
+        let lines: Vec<String> = (0..1_000_000)
+            .map(|_| {
+                (0..5)
+                    .map(|_| symbols[next(symbols.len() as u64) as usize])
+                    .collect()
+            })
+            .collect();
+
+        let start = Instant::now();
+        let hands: Vec<Hand> = lines
+            .iter()
+            .map(|line| Hand::from_str(line, &ruleset).unwrap())
+            .collect();
+        println!("Parse and classify: {:?}", start.elapsed());
+
+        let mut by_key: Vec<&Hand> = hands.iter().collect();
+        let start = Instant::now();
+        by_key.sort_by_key(|hand| hand.sort_key());
+        println!("Sort by cached key: {:?}", start.elapsed());
+
+        // The old way: classify both hands on every comparison.
+        let mut reclassified: Vec<&Hand> = hands.iter().collect();
+        let start = Instant::now();
+        reclassified.sort_by(|a, b| {
+            let key = |hand: &Hand| (classify(&hand.cards).0, ruleset_ranks(hand, &ruleset));
+            key(a).cmp(&key(b))
+        });
+        println!("Sort reclassifying per comparison: {:?}", start.elapsed());
+
+        let sort_keys =
+            |hands: Vec<&Hand>| hands.into_iter().map(Hand::sort_key).collect::<Vec<_>>();
+        assert_eq!(sort_keys(by_key), sort_keys(reclassified));
+    }
+
+    fn ruleset_ranks(hand: &Hand, ruleset: &Ruleset) -> Vec<u8> {
+        hand.symbols
+            .chars()
+            .map(|symbol| ruleset.parse_card(symbol).unwrap().1)
+            .collect()
+    }
+
+    #[test]
+    fn test_general_sizes() {
+        let joker_wild = Ruleset::joker_wild();
+        let hand = |cards: &str| Hand::from_str(cards, &joker_wild).unwrap();
+        assert_eq!(hand("KK").signature().counts(), [2]);
+        assert_eq!(hand("KKQQJ7").signature().counts(), [3, 2, 1]);
+        assert_eq!(hand("KKQQJ7").get_type(), None);
+        assert_eq!(hand("J").signature().counts(), [1]);
+        assert!(hand("KKKQQQ") < hand("KKKKQ2"));
+        // Four and four beats three, three and two, whatever the cards.
+        assert!(hand("AAA22233") < hand("22223333"));
+        assert!(hand("KKKQQQ") < hand("KKKQQQJ"));
+    }
+
+    #[test]
+    fn test_explain() {
+        let joker_wild = Ruleset::joker_wild();
+        let explanation = Hand::from_str("KTJJT", &joker_wild).unwrap().explain();
+        assert_eq!(explanation.signature, Signature::from_counts([4, 1]));
+        assert_eq!(explanation.substitute, Some(Ten));
+        assert_eq!(explanation.effective_cards, vec![King, Ten, Ten, Ten, Ten]);
+        assert_eq!(
+            explanation.to_string(),
+            "4+1 (FourKind) with jokers as T (KTTTT)"
+        );
+
+        let explanation = Hand::from_str("JJJJJ", &joker_wild).unwrap().explain();
+        assert_eq!(
+            explanation.to_string(),
+            "5 (FiveKind) with jokers as A (AAAAA)"
+        );
+        let explanation = Hand::from_str("32T3K", &joker_wild).unwrap().explain();
+        assert_eq!(explanation.to_string(), "2+1+1+1 (OnePair)");
+    }
+
+    #[test]
+    fn test_jokers_match_best_substitution() {
+        let joker_wild = Ruleset::joker_wild();
+        let symbols: Vec<char> = "J234".chars().collect();
+        let mut next = lcg(44);
+        for _ in 0..200 {
+            let size = 1 + next(7) as usize;
+            let line: String = (0..size)
+                .map(|_| symbols[next(symbols.len() as u64) as usize])
+                .collect();
+            // Try every card for every joker separately.
+            let joker_count = line.matches('J').count() as u32;
+            let best = (0..4u64.pow(joker_count))
+                .map(|mut choice| {
+                    let cards: Vec<Card> = Hand::from_str(&line, &joker_wild)
+                        .unwrap()
+                        .cards
+                        .iter()
+                        .map(|card| {
+                            if *card != Joker {
+                                return *card;
+                            }
+                            // Any card that isn't in the hand is as good as any other.
+                            let substitute = [Two, Three, Four, Ace][(choice % 4) as usize];
+                            choice /= 4;
+                            substitute
+                        })
+                        .collect();
+                    Signature::from_counts(get_card_counts(&cards).into_values())
+                })
+                .max()
+                .unwrap();
+            assert_eq!(
+                Hand::from_str(&line, &joker_wild).unwrap().signature(),
+                &best,
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_standings() {
+        let hands = rank_hands(EXAMPLE.iter(), &Ruleset::joker_wild());
+        let table = standings(&hands);
+        assert_eq!(
+            table[4],
+            Standing {
+                rank: 5,
+                hand: "KTJJT".to_string(),
+                hand_type: "FourKind".to_string(),
+                bid: 220,
+                winnings: 1100,
+            }
+        );
+        assert_eq!(table.iter().map(|s| s.winnings).sum::<Num>(), 5905);
+
+        let csv = render_standings(&table, TableFormat::Csv);
+        let csv_lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(csv_lines[0], "rank,hand,type,bid,winnings");
+        assert_eq!(csv_lines[1], "1,32T3K,OnePair,765,765");
+        assert_eq!(csv_lines.len(), 6);
+
+        let text = render_standings(&table, TableFormat::Text);
+        let text_lines: Vec<&str> = text.lines().collect();
+        assert_eq!(text_lines[0], "rank  hand   type      bid  winnings");
+        assert_eq!(text_lines[1], "   1  32T3K  OnePair   765       765");
+        assert_eq!(text_lines[5], "   5  KTJJT  FourKind  220      1100");
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let table = [Standing {
+            rank: 1,
+            hand: "2\"2,".to_string(),
+            hand_type: "2,1".to_string(),
+            bid: 7,
+            winnings: 7,
+        }];
+        let csv = render_standings(&table, TableFormat::Csv);
+        assert_eq!(csv.lines().nth(1), Some(r#"1,"2""2,","2,1",7,7"#));
     }
 }
@@ -1,11 +1,30 @@
 use crate::cursor_grid::{Direction::*, *};
 use std::collections::HashSet;
 
+pub type Num = u32;
 type Node = Option<Pipe>;
 type Row = Vec<Node>;
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Pipe {
     pub connections: HashSet<Direction>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MazeError {
+    NoStart,
+    /// The given start position isn't a pipe, so no loop can run through it.
+    NotAPipe(Coord),
+    /// None of the pipe shapes at the start position close a loop.
+    NoLoopThroughStart(Coord),
+    /// Walking from `from` led to `to`, which isn't a pipe connecting back.
+    DeadEnd {
+        from: Coord,
+        to: Coord,
+    },
+}
+
+const PIPE_CHARS: [char; 6] = ['|', '-', 'L', 'J', '7', 'F'];
+
 impl Pipe {
     pub fn from_char(c: &char) -> Option<Self> {
         let connections = match c {
@@ -21,40 +40,53 @@ impl Pipe {
             connections: HashSet::from(connections),
         })
     }
+
+    pub fn all_shapes() -> impl Iterator<Item = Self> {
+        PIPE_CHARS.iter().filter_map(Self::from_char)
+    }
+
+    /// One of the pipe's exits, chosen the same way every time.
+    pub fn first_connection(&self) -> Direction {
+        *[Up, Down, Left, Right]
+            .iter()
+            .find(|dir| self.connections.contains(dir))
+            .expect("Pipe has no connections")
+    }
+}
+
+/// The pipe at `pos`, or an error if there's none there.
+fn pipe_at(pos: Coord, grid: &Grid<Node>) -> Result<&Pipe, MazeError> {
+    if !grid.is_within_bounds(pos) {
+        return Err(MazeError::NotAPipe(pos));
+    }
+    grid.get(pos).as_ref().ok_or(MazeError::NotAPipe(pos))
 }
 
 /// get cursors on start pos facing connected adjacent pipes
-pub fn get_initial_cursors(start_pos: Coord, grid: &Grid<Node>) -> Vec<Cursor> {
-    let start_pipe = grid.get(start_pos).as_ref().expect("No start pipe");
-    start_pipe
+pub fn get_initial_cursors(start_pos: Coord, grid: &Grid<Node>) -> Result<Vec<Cursor>, MazeError> {
+    let start_pipe = pipe_at(start_pos, grid)?;
+    Ok(start_pipe
         .connections
         .iter()
         .map(|dir| (start_pos, *dir))
-        .collect()
+        .collect())
 }
 
-fn infer_pipe_from_neighbors(pos: Coord, grid: &Grid<Node>) -> Node {
-    let directions_to_connected_neighbor_pipes: HashSet<Direction> = grid
-        .valid_neighbor_cursors(pos)
-        .filter(|(neighbor_pos, dir)| {
-            let maybe_neighbor_pipe = grid.get(*neighbor_pos);
-            maybe_neighbor_pipe
-                .as_ref()
-                .filter(|p| p.connections.contains(&dir.flipped()))
-                .is_some()
-        })
-        .map(|(_, dir)| dir)
-        .collect();
-    if directions_to_connected_neighbor_pipes.len() == 2 {
-        Some(Pipe {
-            connections: directions_to_connected_neighbor_pipes,
-        })
-    } else {
-        None
+/// Try every pipe shape at `start_pos` and keep the first one that closes a loop.
+fn infer_start_pipe(start_pos: Coord, grid: &mut Grid<Node>) -> Result<(), MazeError> {
+    for candidate in Pipe::all_shapes() {
+        grid.set(start_pos, Some(candidate));
+        if walk_loop(start_pos, grid).is_ok() {
+            return Ok(());
+        }
     }
+    grid.set(start_pos, None);
+    Err(MazeError::NoLoopThroughStart(start_pos))
 }
 
-pub fn parse_grid(lines: impl Iterator<Item = impl AsRef<str>>) -> (Grid<Node>, Coord) {
+pub fn parse_grid(
+    lines: impl Iterator<Item = impl AsRef<str>>,
+) -> Result<(Grid<Node>, Coord), MazeError> {
     let mut grid = Grid::new();
     let mut maybe_start_pos = None;
     for (row_idx, line) in lines.enumerate() {
@@ -64,13 +96,11 @@ pub fn parse_grid(lines: impl Iterator<Item = impl AsRef<str>>) -> (Grid<Node>,
             maybe_start_pos = Some((row_idx as i32, col_idx as i32));
         }
     }
-    let start_pos = maybe_start_pos.expect("No start pos");
+    let start_pos = maybe_start_pos.ok_or(MazeError::NoStart)?;
     // Start location actually represents a pipe connected to its neighbors.
     // Lets insert it now.
-    let start_pipe = infer_pipe_from_neighbors(start_pos, &grid)
-        .expect("Start pos isn't connected to exactly 2 pipes");
-    grid.rows[start_pos.0 as usize][start_pos.1 as usize] = Some(start_pipe);
-    (grid, start_pos)
+    infer_start_pipe(start_pos, &mut grid)?;
+    Ok((grid, start_pos))
 }
 
 pub fn parse_row(line: &str) -> (Row, Option<usize>) {
@@ -90,65 +120,447 @@ pub fn parse_row(line: &str) -> (Row, Option<usize>) {
     (row, maybe_start_col)
 }
 
+/// Walks a pipe loop from `start`, yielding each tile once.
+/// Yields an error and stops if the pipes don't lead back to `start`.
 pub struct PipeLoopIterator<'a> {
     start: Coord,
-    current_cursor: Option<Cursor>,
+    current_cursor: Option<Result<Cursor, MazeError>>,
     grid: &'a Grid<Node>,
 }
 
 impl<'a> PipeLoopIterator<'a> {
     pub fn new(start: Coord, grid: &'a Grid<Node>) -> Self {
-        let start_direction = grid
-            .get(start)
-            .as_ref()
-            .map(|pipe| *pipe.connections.iter().next().unwrap());
-        let current_cursor = start_direction.map(|dir| (start, dir));
+        let start_cursor = pipe_at(start, grid).map(|pipe| (start, pipe.first_connection()));
         Self {
             start,
-            current_cursor,
+            current_cursor: Some(start_cursor),
+            grid,
+        }
+    }
+
+    /// Walk the loop starting at the cursor's position, leaving in the cursor's direction.
+    pub fn from_cursor(cursor: Cursor, grid: &'a Grid<Node>) -> Self {
+        Self {
+            start: cursor.0,
+            current_cursor: Some(Ok(cursor)),
             grid,
         }
     }
 }
 
 impl<'a> Iterator for PipeLoopIterator<'a> {
-    type Item = (Coord, &'a Pipe);
+    type Item = Result<(Coord, &'a Pipe), MazeError>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_cursor.is_none() {
-            return None;
-        }
-        let (pos, _) = self.current_cursor.unwrap();
-        let pipe = self.grid.get(pos).as_ref();
-        let result = pipe.map(|p| (pos, p));
-        let next_cursor = self
-            .current_cursor
-            .map(|c| c.next(&self.grid))
+        let cursor = match self.current_cursor.take()? {
+            Ok(cursor) => cursor,
+            Err(err) => return Some(Err(err)),
+        };
+        let (pos, _) = cursor;
+        let pipe = match pipe_at(pos, self.grid) {
+            Ok(pipe) => pipe,
+            Err(err) => return Some(Err(err)),
+        };
+        self.current_cursor = match cursor.next(self.grid) {
             // When next is start, we've completed the loop.
-            .filter(|c| c.0 != self.start);
-        self.current_cursor = next_cursor;
-        return result;
+            Ok(next_cursor) if next_cursor.0 == self.start => None,
+            next_cursor => Some(next_cursor),
+        };
+        Some(Ok((pos, pipe)))
+    }
+}
+
+/// Positions of the loop through `start`, in walking order.
+pub fn walk_loop(start: Coord, grid: &Grid<Node>) -> Result<Vec<Coord>, MazeError> {
+    PipeLoopIterator::new(start, grid)
+        .map(|step| step.map(|(pos, _)| pos))
+        .collect()
+}
+
+/// Every closed loop in the grid, each starting from its first tile in reading order.
+pub fn find_loops(grid: &Grid<Node>) -> Vec<Vec<Coord>> {
+    let mut visited: HashSet<Coord> = HashSet::new();
+    let mut loops = Vec::new();
+    for pos in grid.positions() {
+        if grid.get(pos).is_none() || visited.contains(&pos) {
+            continue;
+        }
+        let mut walked = Vec::new();
+        let mut is_closed = true;
+        for step in PipeLoopIterator::new(pos, grid) {
+            match step {
+                Ok((step_pos, _)) => walked.push(step_pos),
+                Err(_) => is_closed = false,
+            }
+        }
+        // Tiles on a dead-end walk can't be part of any loop, so they're done either way.
+        visited.extend(walked.iter().copied());
+        if is_closed {
+            loops.push(walked);
+        }
     }
+    loops
+}
+
+/// Distance along the loop from the start to every loop tile, taking the shorter way around.
+pub struct LoopDistances {
+    pub distances: Grid<Option<Num>>,
+    pub farthest: Vec<Coord>,
+    pub farthest_distance: Num,
+    pub loop_length: usize,
+}
+
+impl LoopDistances {
+    /// Distances drawn over the grid, with `.` for tiles not on the loop.
+    pub fn render_overlay(&self) -> String {
+        let width = self.farthest_distance.to_string().len();
+        self.distances
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|maybe_distance| match maybe_distance {
+                        Some(distance) => format!("{:>width$}", distance),
+                        None => format!("{:>width$}", "."),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(if width > 1 { " " } else { "" })
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub fn loop_distances(start: Coord, grid: &Grid<Node>) -> Result<LoopDistances, MazeError> {
+    let mut distances: Grid<Option<Num>> = Grid {
+        rows: grid.rows.iter().map(|row| vec![None; row.len()]).collect(),
+    };
+    let mut loop_length = 0;
+    // Walk both ways around and keep the shorter distance for each tile.
+    for cursor in get_initial_cursors(start, grid)? {
+        let walked = PipeLoopIterator::from_cursor(cursor, grid)
+            .map(|step| step.map(|(pos, _)| pos))
+            .collect::<Result<Vec<Coord>, MazeError>>()?;
+        loop_length = walked.len();
+        for (distance, pos) in walked.into_iter().enumerate() {
+            let distance = distance as Num;
+            let shortest = distances
+                .get(pos)
+                .map_or(distance, |prev| prev.min(distance));
+            distances.set(pos, Some(shortest));
+        }
+    }
+    let farthest_distance = distances.iter().flatten().copied().max().unwrap_or(0);
+    let farthest = distances
+        .positions()
+        .filter(|pos| *distances.get(*pos) == Some(farthest_distance))
+        .collect();
+    Ok(LoopDistances {
+        distances,
+        farthest,
+        farthest_distance,
+        loop_length,
+    })
 }
 
 pub trait GridIterator<Node> {
-    fn next(self: &Self, grid: &Grid<Node>) -> Self;
+    fn next(self: &Self, grid: &Grid<Node>) -> Result<Self, MazeError>
+    where
+        Self: Sized;
 }
 
 impl GridIterator<Node> for Cursor {
     /// Move from current pos to next pos, then face pipe's other exit
-    fn next(self: &Self, grid: &Grid<Node>) -> Self {
+    fn next(self: &Self, grid: &Grid<Node>) -> Result<Self, MazeError> {
         let (coord, dir) = self;
         let next_coord = *coord + *dir;
-        let maybe_next_pipe = grid.get(next_coord);
-        if let Some(next_pipe) = maybe_next_pipe {
-            let mut dirset = next_pipe.connections.clone();
-            dirset.remove(&dir.flipped());
-            assert_eq!(dirset.len(), 1);
-            let next_dir = dirset.into_iter().next().unwrap();
-            return (next_coord, next_dir);
-        } else {
-            // This can only happen if we get back to start, or the map is wrong, and that shouldn't happen in this program
-            panic!();
+        let dead_end = MazeError::DeadEnd {
+            from: *coord,
+            to: next_coord,
+        };
+        if !grid.is_within_bounds(next_coord) {
+            return Err(dead_end);
         }
+        let next_pipe = grid.get(next_coord).as_ref().ok_or(dead_end)?;
+        let mut dirset = next_pipe.connections.clone();
+        if !dirset.remove(&dir.flipped()) {
+            // The next pipe doesn't connect back to us.
+            return Err(dead_end);
+        }
+        assert_eq!(dirset.len(), 1);
+        let next_dir = dirset.into_iter().next().unwrap();
+        Ok((next_coord, next_dir))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileClass {
+    Loop,
+    Inside,
+    Outside,
+}
+
+/// Classify every tile against the given loop.
+/// Scans each row left to right, flipping inside/outside whenever a loop tile connects upwards.
+pub fn classify_tiles(grid: &Grid<Node>, pipe_loop: &[Coord]) -> Grid<TileClass> {
+    let loop_tiles: HashSet<Coord> = pipe_loop.iter().copied().collect();
+    let mut classes = Grid::new();
+    for (row_idx, row) in grid.rows.iter().enumerate() {
+        let mut is_inside = false;
+        let mut class_row = Vec::with_capacity(row.len());
+        for (col_idx, node) in row.iter().enumerate() {
+            let pos = (row_idx as i32, col_idx as i32);
+            if loop_tiles.contains(&pos) {
+                if node.as_ref().is_some_and(|p| p.connections.contains(&Up)) {
+                    is_inside = !is_inside;
+                }
+                class_row.push(TileClass::Loop);
+            } else if is_inside {
+                class_row.push(TileClass::Inside);
+            } else {
+                class_row.push(TileClass::Outside);
+            }
+        }
+        classes.rows.push(class_row);
+    }
+    classes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Unicode box-drawing characters and shade blocks.
+    BoxDrawing,
+    /// Plain ASCII for terminals that can't show the above.
+    Ascii,
+}
+
+impl Charset {
+    fn tile_char(&self, tile: Tile) -> char {
+        match (self, tile) {
+            (Charset::BoxDrawing, Tile::Ground) => ' ',
+            (Charset::BoxDrawing, Tile::Inside) => '▓',
+            (Charset::BoxDrawing, Tile::Outside) => '░',
+            (Charset::Ascii, Tile::Ground) => '.',
+            (Charset::Ascii, Tile::Inside) => 'I',
+            (Charset::Ascii, Tile::Outside) => 'O',
+            (charset, Tile::Pipe(pipe)) => {
+                let has = |dir| pipe.connections.contains(&dir);
+                let (box_char, ascii_char) = match (has(Up), has(Down), has(Left), has(Right)) {
+                    (true, false, true, false) => ('┘', '+'),
+                    (true, false, false, true) => ('└', '+'),
+                    (false, true, false, true) => ('┌', '+'),
+                    (false, true, true, false) => ('┐', '+'),
+                    (true, true, false, false) => ('│', '|'),
+                    (false, false, true, true) => ('─', '-'),
+                    _ => unreachable!("Pipes have exactly two connections"),
+                };
+                match charset {
+                    Charset::BoxDrawing => box_char,
+                    Charset::Ascii => ascii_char,
+                }
+            }
+        }
+    }
+}
+
+enum Tile<'a> {
+    Pipe(&'a Pipe),
+    Ground,
+    Inside,
+    Outside,
+}
+
+/// Draw the grid as text.
+/// With `classes`, only loop pipes are drawn and every other tile is shaded by inside/outside.
+pub fn render_grid(
+    grid: &Grid<Node>,
+    classes: Option<&Grid<TileClass>>,
+    charset: Charset,
+) -> String {
+    let mut lines = Vec::new();
+    for row_idx in 0..grid.rows.len() {
+        let line: String = grid.rows[row_idx]
+            .iter()
+            .enumerate()
+            .map(|(col_idx, node)| {
+                let pos = (row_idx as i32, col_idx as i32);
+                let tile = match (classes.map(|c| c.get(pos)), node) {
+                    (Some(TileClass::Inside), _) => Tile::Inside,
+                    (Some(TileClass::Outside), _) => Tile::Outside,
+                    (_, Some(pipe)) => Tile::Pipe(pipe),
+                    (_, None) => Tile::Ground,
+                };
+                charset.tile_char(tile)
+            })
+            .collect();
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_grid_infers_start() {
+        let input = ["-L|F7", "7S-7|", "L|7||", "-L-J|", "L|-JF"];
+        let (grid, start_pos) = parse_grid(input.iter()).unwrap();
+        assert_eq!(start_pos, (1, 1));
+        assert_eq!(grid.get(start_pos), &Pipe::from_char(&'F'));
+        assert_eq!(walk_loop(start_pos, &grid).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_parse_grid_ambiguous_start() {
+        // Start has three connecting neighbors, but only one shape closes a loop.
+        let input = [".....", ".F-7.", "-S.|.", ".L-J.", "....."];
+        let (grid, start_pos) = parse_grid(input.iter()).unwrap();
+        assert_eq!(grid.get(start_pos), &Pipe::from_char(&'|'));
+    }
+
+    #[test]
+    fn test_parse_grid_errors() {
+        let no_start = [".F7.", ".LJ."];
+        assert_eq!(parse_grid(no_start.iter()).err(), Some(MazeError::NoStart));
+        let no_loop = ["....", ".S-.", "...."];
+        assert_eq!(
+            parse_grid(no_loop.iter()).err(),
+            Some(MazeError::NoLoopThroughStart((1, 1)))
+        );
+    }
+
+    #[test]
+    fn test_dead_end() {
+        let input = ["F-7", "|.|", "L-."];
+        let grid = Grid {
+            rows: input.iter().map(|line| parse_row(line).0).collect(),
+        };
+        assert_eq!(
+            walk_loop((0, 0), &grid),
+            Err(MazeError::DeadEnd {
+                from: (2, 1),
+                to: (2, 2)
+            })
+        );
+    }
+
+    #[test]
+    fn test_not_a_pipe() {
+        let input = ["F7.", "LJ."];
+        let grid = Grid {
+            rows: input.iter().map(|line| parse_row(line).0).collect(),
+        };
+        assert_eq!(walk_loop((0, 2), &grid), Err(MazeError::NotAPipe((0, 2))));
+        assert_eq!(walk_loop((5, 5), &grid), Err(MazeError::NotAPipe((5, 5))));
+        assert_eq!(
+            loop_distances((1, 2), &grid).err(),
+            Some(MazeError::NotAPipe((1, 2)))
+        );
+        assert_eq!(walk_loop((0, 0), &grid).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_loop_distances() {
+        let input = ["..F7.", ".FJ|.", "SJ.L7", "|F--J", "LJ..."];
+        let (grid, start_pos) = parse_grid(input.iter()).unwrap();
+        let result = loop_distances(start_pos, &grid).unwrap();
+        assert_eq!(result.loop_length, 16);
+        assert_eq!(result.farthest_distance, 8);
+        assert_eq!(result.farthest, vec![(2, 4)]);
+        let expected_overlay = "..45.\n.236.\n01.78\n14567\n23...";
+        assert_eq!(result.render_overlay(), expected_overlay);
+    }
+
+    #[test]
+    fn test_render_grid() {
+        let input = [
+            "..........",
+            ".S------7.",
+            ".|F----7|.",
+            ".||-...||.",
+            ".||....||.",
+            ".|L-7F-J|.",
+            ".|..||..|.",
+            ".L--JL--J.",
+            "..........",
+        ];
+        let (grid, start_pos) = parse_grid(input.iter()).unwrap();
+        let pipe_loop = walk_loop(start_pos, &grid).unwrap();
+        let classes = classify_tiles(&grid, &pipe_loop);
+        let inside_count = classes
+            .iter()
+            .filter(|class| **class == TileClass::Inside)
+            .count();
+        assert_eq!(inside_count, 4);
+
+        let expected_plain = [
+            "          ",
+            " ┌──────┐ ",
+            " │┌────┐│ ",
+            " ││─   ││ ",
+            " ││    ││ ",
+            " │└─┐┌─┘│ ",
+            " │  ││  │ ",
+            " └──┘└──┘ ",
+            "          ",
+        ];
+        assert_eq!(
+            render_grid(&grid, None, Charset::BoxDrawing),
+            expected_plain.join("\n")
+        );
+
+        let expected_shaded = [
+            "OOOOOOOOOO",
+            "O+------+O",
+            "O|+----+|O",
+            "O||OOOO||O",
+            "O||OOOO||O",
+            "O|+-++-+|O",
+            "O|II||II|O",
+            "O+--++--+O",
+            "OOOOOOOOOO",
+        ];
+        assert_eq!(
+            render_grid(&grid, Some(&classes), Charset::Ascii),
+            expected_shaded.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_find_loops() {
+        let input = ["F7.F-7", "LJ.|.|", "-7.L-J", "F|...."];
+        let grid = Grid {
+            rows: input.iter().map(|line| parse_row(line).0).collect(),
+        };
+        let loops = find_loops(&grid);
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loops[0][0], (0, 0));
+        assert_eq!(loops[0].len(), 4);
+        assert_eq!(loops[1][0], (0, 3));
+        assert_eq!(loops[1].len(), 8);
+    }
+
+    #[test]
+    fn test_enclosed_example() {
+        let input = [
+            ".F----7F7F7F7F-7....",
+            ".|F--7||||||||FJ....",
+            ".||.FJ||||||||L7....",
+            "FJL7L7LJLJ||LJ.L-7..",
+            "L--J.L7...LJS7F-7L7.",
+            "....F-J..F7FJ|L7L7L7",
+            "....L7.F7||L7|.L7L7|",
+            ".....|FJLJ|FJ|F7|.LJ",
+            "....FJL-7.||.||||...",
+            "....L---J.LJ.LJLJ...",
+        ];
+        let (grid, start_pos) = parse_grid(input.iter()).unwrap();
+        let pipe_loop = walk_loop(start_pos, &grid).unwrap();
+        let inside_count = classify_tiles(&grid, &pipe_loop)
+            .iter()
+            .filter(|class| **class == TileClass::Inside)
+            .count();
+        assert_eq!(inside_count, 8);
+        assert_eq!(crate::geometry::interior_point_count(&pipe_loop), 8);
     }
 }
@@ -1,8 +1,9 @@
-use memoize::memoize;
+use crate::lcg::lcg_step;
 use regex_macro::regex;
+use std::collections::HashMap;
 use Fountain::*;
 
-pub type Num = u64;
+pub type Num = u128;
 pub type Fountains = Vec<Fountain>;
 pub type Groups = Vec<u8>;
 
@@ -14,8 +15,7 @@ pub enum Fountain {
 }
 
 impl Fountain {
-    fn parse(s: &str) -> Option<Self> {
-        assert!(s.len() == 1);
+    pub fn parse(s: &str) -> Option<Self> {
         match s {
             r"." => Some(Self::Good),
             r"#" => Some(Self::Broken),
@@ -23,6 +23,22 @@ impl Fountain {
             _ => None,
         }
     }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Good => '.',
+            Broken => '#',
+            Unknown => '?',
+        }
+    }
+}
+
+/// Draw a row of fountains as `.#?` characters.
+pub fn render_fountains(fountains: &[Fountain]) -> String {
+    fountains
+        .iter()
+        .map(|fountain| fountain.to_char())
+        .collect()
 }
 
 pub fn parse_fountain_line(line: &str) -> (Vec<Fountain>, Vec<u8>) {
@@ -42,52 +58,499 @@ pub fn parse_fountain_line(line: &str) -> (Vec<Fountain>, Vec<u8>) {
     result
 }
 
-pub fn get_possibility_count(fountains: &Fountains, groups: &Groups) -> Num {
-    // This extra fountain is needed to extend iteration because our window size is one larger to include a terminating "good" fountain.
-    let mut fountains_extended: Fountains = fountains.clone();
-    fountains_extended.push(Good);
-    get_possibility_count_helper(fountains_extended, groups.clone())
+pub fn get_possibility_count(fountains: &[Fountain], groups: &[u8]) -> Num {
+    checked_possibility_count(fountains, groups).expect("Possibility count overflowed")
+}
+
+/// Repeat a row `factor` times joined by `separator`, with its groups repeated to match.
+pub fn unfold(
+    fountains: &[Fountain],
+    groups: &[u8],
+    factor: usize,
+    separator: Fountain,
+) -> (Fountains, Groups) {
+    let mut unfolded_fountains = Vec::with_capacity((fountains.len() + 1) * factor);
+    for copy_idx in 0..factor {
+        if copy_idx > 0 {
+            unfolded_fountains.push(separator);
+        }
+        unfolded_fountains.extend_from_slice(fountains);
+    }
+    (unfolded_fountains, groups.repeat(factor))
+}
+
+/// Counts arrangements of a row after unfolding it.
+pub fn unfolded_possibility_count(
+    fountains: &[Fountain],
+    groups: &[u8],
+    factor: usize,
+    separator: Fountain,
+) -> Result<Num, &'static str> {
+    let (unfolded_fountains, unfolded_groups) = unfold(fountains, groups, factor, separator);
+    checked_possibility_count(&unfolded_fountains, &unfolded_groups)
+        .ok_or("Possibility count overflowed")
+}
+
+/// Counts arrangements with a DP over (fountain index, group index), or `None` on overflow.
+pub fn checked_possibility_count(fountains: &[Fountain], groups: &[u8]) -> Option<Num> {
+    let table = CountTable::new(fountains, groups);
+    (!table.overflowed).then(|| table.get(0, 0))
+}
+
+/// `get(i, j)` is the number of ways to place `groups[j..]` in `fountains[i..]`,
+/// where fountain `i` is free to start a new group.
+/// Counts saturate at `Num::MAX` if they overflow.
+struct CountTable<'a> {
+    fountains: &'a [Fountain],
+    groups: &'a [u8],
+    /// Length of the run of possibly-broken fountains starting at each index.
+    unbroken_run: Vec<usize>,
+    counts: Vec<Num>,
+    overflowed: bool,
 }
 
-#[memoize]
-fn get_possibility_count_helper(fountains: Fountains, groups: Groups) -> Num {
-    if groups.len() == 0 {
-        // It's our duty to make sure there are no more broken fountains (that would require more groups declared)
-        if fountains.iter().all(|fountain| *fountain != Broken) {
-            return 1;
+impl<'a> CountTable<'a> {
+    fn new(fountains: &'a [Fountain], groups: &'a [u8]) -> Self {
+        let fountain_count = fountains.len();
+        let group_count = groups.len();
+        let mut unbroken_run = vec![0; fountain_count + 1];
+        // Whether any known broken fountain remains from each index.
+        let mut broken_remaining = vec![false; fountain_count + 1];
+        for idx in (0..fountain_count).rev() {
+            unbroken_run[idx] = if fountains[idx] == Good {
+                0
+            } else {
+                unbroken_run[idx + 1] + 1
+            };
+            broken_remaining[idx] = fountains[idx] == Broken || broken_remaining[idx + 1];
+        }
+        let mut table = Self {
+            fountains,
+            groups,
+            unbroken_run,
+            counts: vec![0; (fountain_count + 1) * (group_count + 1)],
+            overflowed: false,
+        };
+
+        for idx in (0..=fountain_count).rev() {
+            // No groups left: valid only if there are no more broken fountains.
+            table.set(idx, group_count, (!broken_remaining[idx]).into());
+            if idx == fountain_count {
+                continue;
+            }
+            for group_idx in (0..group_count).rev() {
+                let count = match table.group_end(idx, group_idx) {
+                    Some(next_idx) => table
+                        .skip_count(idx, group_idx)
+                        .checked_add(table.get(next_idx, group_idx + 1)),
+                    None => Some(table.skip_count(idx, group_idx)),
+                };
+                let count = count.unwrap_or_else(|| {
+                    table.overflowed = true;
+                    Num::MAX
+                });
+                table.set(idx, group_idx, count);
+            }
+        }
+        table
+    }
+
+    fn get(&self, idx: usize, group_idx: usize) -> Num {
+        self.counts[idx * (self.groups.len() + 1) + group_idx]
+    }
+
+    fn set(&mut self, idx: usize, group_idx: usize, count: Num) {
+        self.counts[idx * (self.groups.len() + 1) + group_idx] = count;
+    }
+
+    /// Ways to finish if fountain `idx` is left good.
+    fn skip_count(&self, idx: usize, group_idx: usize) -> Num {
+        if self.fountains[idx] == Broken {
+            0
         } else {
-            return 0;
+            self.get(idx + 1, group_idx)
+        }
+    }
+
+    /// If group `group_idx` can start at fountain `idx`, the index after its terminating fountain.
+    /// The group must fit, and must not run into another broken fountain.
+    fn group_end(&self, idx: usize, group_idx: usize) -> Option<usize> {
+        let group_size = self.groups[group_idx] as usize;
+        let group_end = idx + group_size;
+        let fits = self.fountains[idx] != Good
+            && self.unbroken_run[idx] >= group_size
+            && (group_end == self.fountains.len() || self.fountains[group_end] != Broken);
+        fits.then(|| (group_end + 1).min(self.fountains.len()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumerationMode {
+    /// Every arrangement once, in order with `.` before `#`.
+    InOrder,
+    /// Random arrangements forever, each equally likely, repeats allowed.
+    /// Only approximately uniform if the total count overflows.
+    Sample { seed: u64 },
+}
+
+/// Lazily yields each concrete arrangement of a row matching its groups.
+pub struct Arrangements<'a> {
+    table: CountTable<'a>,
+    mode: EnumerationMode,
+    remaining: Option<usize>,
+    /// Partial arrangements still to explore, with the next fountain and group indices.
+    stack: Vec<(Fountains, usize, usize)>,
+    rng_state: u64,
+}
+
+pub fn arrangements<'a>(
+    fountains: &'a [Fountain],
+    groups: &'a [u8],
+    mode: EnumerationMode,
+) -> Arrangements<'a> {
+    let table = CountTable::new(fountains, groups);
+    let mut stack = Vec::new();
+    if table.get(0, 0) > 0 {
+        stack.push((Vec::with_capacity(fountains.len()), 0, 0));
+    }
+    let rng_state = match mode {
+        EnumerationMode::InOrder => 0,
+        EnumerationMode::Sample { seed } => seed,
+    };
+    Arrangements {
+        table,
+        mode,
+        remaining: None,
+        stack,
+        rng_state,
+    }
+}
+
+impl<'a> Arrangements<'a> {
+    /// Stop after yielding at most `limit` arrangements.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.remaining = Some(limit);
+        self
+    }
+
+    fn next_in_order(&mut self) -> Option<Fountains> {
+        let fountain_count = self.table.fountains.len();
+        let group_count = self.table.groups.len();
+        while let Some((mut arrangement, idx, group_idx)) = self.stack.pop() {
+            if group_idx == group_count {
+                arrangement.resize(fountain_count, Good);
+                return Some(arrangement);
+            }
+            // Push the group branch first, so the good branch is explored first.
+            if let Some(next_idx) = self.table.group_end(idx, group_idx) {
+                if self.table.get(next_idx, group_idx + 1) > 0 {
+                    let mut with_group = arrangement.clone();
+                    self.push_group(&mut with_group, idx, group_idx);
+                    self.stack.push((with_group, next_idx, group_idx + 1));
+                }
+            }
+            if self.table.skip_count(idx, group_idx) > 0 {
+                arrangement.push(Good);
+                self.stack.push((arrangement, idx + 1, group_idx));
+            }
         }
+        None
     }
-    let mut counter = 0;
-    let current_group_size = groups[0] as usize;
-    let window_size = current_group_size + 1;
-    for (idx, window) in fountains.windows(window_size).enumerate() {
-        let mut window_permutations = 0;
-        let can_group_fit_window = window[0..current_group_size]
+
+    fn next_sample(&mut self) -> Option<Fountains> {
+        let fountain_count = self.table.fountains.len();
+        let group_count = self.table.groups.len();
+        if self.table.get(0, 0) == 0 {
+            return None;
+        }
+        let mut arrangement = Vec::with_capacity(fountain_count);
+        let (mut idx, mut group_idx) = (0, 0);
+        while group_idx < group_count {
+            let skip_count = self.table.skip_count(idx, group_idx);
+            let total = self.table.get(idx, group_idx);
+            // Choose a branch with probability proportional to how many arrangements it leads to.
+            if self.random_below(total) < skip_count {
+                arrangement.push(Good);
+                idx += 1;
+            } else {
+                let next_idx = self.table.group_end(idx, group_idx).unwrap();
+                self.push_group(&mut arrangement, idx, group_idx);
+                idx = next_idx;
+                group_idx += 1;
+            }
+        }
+        arrangement.resize(fountain_count, Good);
+        Some(arrangement)
+    }
+
+    /// Append a group of broken fountains starting at `idx`, plus its terminating good fountain.
+    fn push_group(&self, arrangement: &mut Fountains, idx: usize, group_idx: usize) {
+        let group_size = self.table.groups[group_idx] as usize;
+        arrangement.extend(std::iter::repeat_n(Broken, group_size));
+        if idx + group_size < self.table.fountains.len() {
+            arrangement.push(Good);
+        }
+    }
+
+    fn random_below(&mut self, bound: Num) -> Num {
+        self.rng_state = lcg_step(self.rng_state);
+        let high = self.rng_state;
+        self.rng_state = lcg_step(self.rng_state);
+        let random = ((high as Num) << 64) | self.rng_state as Num;
+        random % bound
+    }
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Fountains;
+
+    fn next(&mut self) -> Option<Fountains> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        let result = match self.mode {
+            EnumerationMode::InOrder => self.next_in_order(),
+            EnumerationMode::Sample { .. } => self.next_sample(),
+        };
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        result
+    }
+}
+
+/// One entry of a flexible group spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupSpec {
+    /// A group sized within `min..=max`, written `3` or `1-3`.
+    /// Optional groups may be left out entirely, written `3?` or `1-3?`.
+    Sized { min: u8, max: u8, optional: bool },
+    /// Any number of groups of any size, written `*`.
+    Wildcard,
+}
+
+impl GroupSpec {
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        if s == "*" {
+            return Ok(Self::Wildcard);
+        }
+        let (sizes, optional) = match s.strip_suffix('?') {
+            Some(sizes) => (sizes, true),
+            None => (s, false),
+        };
+        let (min, max) = match sizes.split_once('-') {
+            Some((min, max)) => (min, max),
+            None => (sizes, sizes),
+        };
+        let min: u8 = min.parse().map_err(|_| "Invalid group size")?;
+        let max: u8 = max.parse().map_err(|_| "Invalid group size")?;
+        if min == 0 || min > max {
+            return Err("Group size range must be within 1..=255 and not empty");
+        }
+        Ok(Self::Sized { min, max, optional })
+    }
+
+    fn is_skippable(&self) -> bool {
+        match self {
+            Self::Sized { optional, .. } => *optional,
+            Self::Wildcard => true,
+        }
+    }
+}
+
+/// Parse a line like `??#?.? 1-2,3?,*`.
+pub fn parse_flexible_line(line: &str) -> Result<(Fountains, Vec<GroupSpec>), &'static str> {
+    let (fountains_str, specs_str) = line
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or("Expected fountains and group specs")?;
+    let fountains = fountains_str
+        .chars()
+        .map(|c| Fountain::parse(&c.to_string()).ok_or("Invalid fountain"))
+        .collect::<Result<Fountains, _>>()?;
+    let specs = specs_str
+        .trim()
+        .split(',')
+        .filter(|spec| !spec.is_empty())
+        .map(|spec| GroupSpec::parse(spec.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((fountains, specs))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AutomatonState {
+    /// Between groups, with specs before this index already satisfied.
+    Gap(usize),
+    /// Inside a group for the spec at this index, having seen this many broken fountains.
+    /// Wildcard groups don't track their size.
+    Run(usize, u8),
+}
+
+type StateSet = Vec<u64>;
+
+fn state_set_contains(state_set: &StateSet, idx: usize) -> bool {
+    state_set[idx / 64] & (1 << (idx % 64)) != 0
+}
+
+/// A row's group specs compiled into a finite automaton over good and broken fountains.
+///
+/// The automaton is nondeterministic, since optional groups and wildcards can match the same
+/// fountains in more than one way. Counting runs it as a DFA over sets of states, so each
+/// arrangement is counted once no matter how many ways it matches.
+pub struct GroupAutomaton {
+    states: Vec<AutomatonState>,
+    /// Targets for each state on a good fountain, then on a broken fountain.
+    transitions: Vec<[Vec<usize>; 2]>,
+    accepting: Vec<bool>,
+}
+
+impl GroupAutomaton {
+    pub fn compile(specs: &[GroupSpec]) -> Self {
+        let spec_count = specs.len();
+        // Whether every spec from each index onwards can be left out.
+        let mut skippable_from = vec![true; spec_count + 1];
+        for idx in (0..spec_count).rev() {
+            skippable_from[idx] = specs[idx].is_skippable() && skippable_from[idx + 1];
+        }
+
+        let mut states: Vec<AutomatonState> = (0..=spec_count).map(AutomatonState::Gap).collect();
+        for (spec_idx, spec) in specs.iter().enumerate() {
+            match spec {
+                GroupSpec::Sized { max, .. } => {
+                    states.extend((1..=*max).map(|size| AutomatonState::Run(spec_idx, size)))
+                }
+                GroupSpec::Wildcard => states.push(AutomatonState::Run(spec_idx, 1)),
+            }
+        }
+        let state_indices: HashMap<AutomatonState, usize> = states
+            .iter()
+            .enumerate()
+            .map(|(idx, state)| (*state, idx))
+            .collect();
+
+        let transitions = states
+            .iter()
+            .map(|state| {
+                let (on_good, on_broken) = match *state {
+                    AutomatonState::Gap(spec_idx) => {
+                        // Start the next group, or any later one if the ones between are skippable.
+                        let mut starts = Vec::new();
+                        for (later_idx, later_spec) in specs.iter().enumerate().skip(spec_idx) {
+                            starts.push(AutomatonState::Run(later_idx, 1));
+                            if !later_spec.is_skippable() {
+                                break;
+                            }
+                        }
+                        (vec![AutomatonState::Gap(spec_idx)], starts)
+                    }
+                    AutomatonState::Run(spec_idx, size) => match specs[spec_idx] {
+                        GroupSpec::Sized { min, max, .. } => {
+                            let on_good = if size >= min {
+                                vec![AutomatonState::Gap(spec_idx + 1)]
+                            } else {
+                                vec![]
+                            };
+                            let on_broken = if size < max {
+                                vec![AutomatonState::Run(spec_idx, size + 1)]
+                            } else {
+                                vec![]
+                            };
+                            (on_good, on_broken)
+                        }
+                        // Wildcards may go on to match another group of their own.
+                        GroupSpec::Wildcard => (
+                            vec![AutomatonState::Gap(spec_idx)],
+                            vec![AutomatonState::Run(spec_idx, 1)],
+                        ),
+                    },
+                };
+                let to_indices = |targets: Vec<AutomatonState>| -> Vec<usize> {
+                    targets.iter().map(|target| state_indices[target]).collect()
+                };
+                [to_indices(on_good), to_indices(on_broken)]
+            })
+            .collect();
+
+        let accepting = states
             .iter()
-            .all(|fountain| *fountain != Good);
-        let is_group_terminated = window[current_group_size] != Broken;
-        if can_group_fit_window && is_group_terminated {
-            window_permutations += 1;
+            .map(|state| match *state {
+                AutomatonState::Gap(spec_idx) => skippable_from[spec_idx],
+                AutomatonState::Run(spec_idx, size) => {
+                    let is_group_complete = match specs[spec_idx] {
+                        GroupSpec::Sized { min, .. } => size >= min,
+                        GroupSpec::Wildcard => true,
+                    };
+                    is_group_complete && skippable_from[spec_idx + 1]
+                }
+            })
+            .collect();
+
+        Self {
+            states,
+            transitions,
+            accepting,
         }
+    }
 
-        let (_, remainder_fountains) = fountains.split_at(idx + window_size);
-        let (_, remainder_groups) = groups.split_at(1);
-        let remainder_permutations = get_possibility_count_helper(
-            Vec::from(remainder_fountains),
-            Vec::from(remainder_groups),
-        );
+    fn start(&self) -> StateSet {
+        let mut start = vec![0; self.states.len().div_ceil(64)];
+        start[0] |= 1;
+        start
+    }
+
+    fn step(&self, current: &StateSet, fountain: Fountain) -> StateSet {
+        let symbol = match fountain {
+            Good => 0,
+            Broken => 1,
+            Unknown => unreachable!("Only known fountains can be stepped over"),
+        };
+        let mut next = vec![0; current.len()];
+        for state_idx in (0..self.states.len()).filter(|idx| state_set_contains(current, *idx)) {
+            for target in self.transitions[state_idx][symbol].iter() {
+                next[target / 64] |= 1 << (target % 64);
+            }
+        }
+        next
+    }
+
+    fn is_accepting(&self, current: &StateSet) -> bool {
+        (0..self.states.len()).any(|idx| state_set_contains(current, idx) && self.accepting[idx])
+    }
 
-        counter += window_permutations * remainder_permutations;
+    /// Whether a fully-defined arrangement satisfies the specs.
+    pub fn matches(&self, arrangement: &[Fountain]) -> bool {
+        let end = arrangement.iter().fold(self.start(), |current, fountain| {
+            self.step(&current, *fountain)
+        });
+        self.is_accepting(&end)
+    }
 
-        // Here, we must check to see if the group is anchored here.
-        // That is, there's a known broken fountain here so we can't iterate futher and consider it part of the same group.
-        if window[0] == Broken {
-            break;
+    /// Number of ways to fill in the unknown fountains so the row satisfies the specs,
+    /// or `None` on overflow.
+    pub fn count_matches(&self, fountains: &[Fountain]) -> Option<Num> {
+        let mut current: HashMap<StateSet, Num> = HashMap::from([(self.start(), 1)]);
+        for fountain in fountains {
+            let choices: &[Fountain] = match fountain {
+                Unknown => &[Good, Broken],
+                known => std::slice::from_ref(known),
+            };
+            let mut next: HashMap<StateSet, Num> = HashMap::new();
+            for (state_set, count) in current.iter() {
+                for choice in choices {
+                    let next_set = self.step(state_set, *choice);
+                    if next_set.iter().any(|bits| *bits != 0) {
+                        let entry = next.entry(next_set).or_default();
+                        *entry = entry.checked_add(*count)?;
+                    }
+                }
+            }
+            current = next;
         }
+        current
+            .iter()
+            .filter(|(state_set, _)| self.is_accepting(state_set))
+            .try_fold(0 as Num, |total, (_, count)| total.checked_add(*count))
     }
-    return counter;
 }
 
 enum FountainSearchState {
@@ -147,23 +610,27 @@ pub fn is_arrangement_valid(fountains: &Fountains, groups: &Groups) -> bool {
             }
         }
     }
-    // All of the groups were valid.
-    return true;
+    // All of the groups were valid, as long as no broken fountains are left over.
+    fountain_iter.all(|fountain_status| *fountain_status != Broken)
 }
 
 #[cfg(test)]
 mod test {
     use super::Fountain::*;
     use super::*;
+    use crate::lcg::lcg;
+    use memoize::memoize;
 
     #[test]
     fn test_parse_fountain_char() {
-        let expected = [Some(Unknown), Some(Broken), Some(Good), None];
+        let expected = [Some(Unknown), Some(Broken), Some(Good), None, None, None];
         let actual = [
             Fountain::parse("?"),
             Fountain::parse("#"),
             Fountain::parse("."),
             Fountain::parse("X"),
+            Fountain::parse(".."),
+            Fountain::parse(""),
         ];
         assert_eq!(&actual, &expected)
     }
@@ -199,6 +666,276 @@ mod test {
         }
     }
 
+    #[memoize]
+    fn memoized_possibility_count_helper(fountains: Fountains, groups: Groups) -> Num {
+        if groups.len() == 0 {
+            if fountains.iter().all(|fountain| *fountain != Broken) {
+                return 1;
+            } else {
+                return 0;
+            }
+        }
+        let mut counter = 0;
+        let current_group_size = groups[0] as usize;
+        let window_size = current_group_size + 1;
+        for (idx, window) in fountains.windows(window_size).enumerate() {
+            let mut window_permutations = 0;
+            let can_group_fit_window = window[0..current_group_size]
+                .iter()
+                .all(|fountain| *fountain != Good);
+            let is_group_terminated = window[current_group_size] != Broken;
+            if can_group_fit_window && is_group_terminated {
+                window_permutations += 1;
+            }
+            let (_, remainder_fountains) = fountains.split_at(idx + window_size);
+            let (_, remainder_groups) = groups.split_at(1);
+            let remainder_permutations = memoized_possibility_count_helper(
+                Vec::from(remainder_fountains),
+                Vec::from(remainder_groups),
+            );
+            counter += window_permutations * remainder_permutations;
+            if window[0] == Broken {
+                break;
+            }
+        }
+        return counter;
+    }
+
+    /// The previous memoized implementation, kept as an oracle for the DP.
+    fn memoized_possibility_count(fountains: &Fountains, groups: &Groups) -> Num {
+        let mut fountains_extended: Fountains = fountains.clone();
+        fountains_extended.push(Good);
+        memoized_possibility_count_helper(fountains_extended, groups.clone())
+    }
+
+    /// Tries every way of filling in the unknown fountains.
+    fn brute_force_possibility_count(fountains: &Fountains, groups: &Groups) -> Num {
+        let unknown_indices: Vec<usize> = (0..fountains.len())
+            .filter(|idx| fountains[*idx] == Unknown)
+            .collect();
+        (0..1u32 << unknown_indices.len())
+            .filter(|mask| {
+                let mut arrangement = fountains.clone();
+                for (bit, idx) in unknown_indices.iter().enumerate() {
+                    arrangement[*idx] = if mask & (1 << bit) != 0 { Broken } else { Good };
+                }
+                is_arrangement_valid(&arrangement, groups)
+            })
+            .count() as Num
+    }
+
+    #[test]
+    fn test_possibility_count_matches_oracles() {
+        let mut next_random = lcg(2023);
+        for _ in 0..300 {
+            let fountains: Fountains = (0..next_random(12) + 1)
+                .map(|_| [Good, Broken, Unknown, Unknown][next_random(4) as usize])
+                .collect();
+            let groups: Groups = (0..next_random(4))
+                .map(|_| next_random(3) as u8 + 1)
+                .collect();
+            let expected = brute_force_possibility_count(&fountains, &groups);
+            assert_eq!(get_possibility_count(&fountains, &groups), expected);
+            assert_eq!(memoized_possibility_count(&fountains, &groups), expected);
+        }
+    }
+
+    #[test]
+    fn test_arrangements_in_order() {
+        let (fountains, groups) = parse_fountain_line("?###???????? 3,2,1");
+        let rendered: Vec<String> = arrangements(&fountains, &groups, EnumerationMode::InOrder)
+            .map(|arrangement| render_fountains(&arrangement))
+            .collect();
+        assert_eq!(rendered.len(), 10);
+        assert_eq!(rendered[0], ".###....##.#");
+        assert_eq!(rendered[9], ".###.##.#...");
+        let mut sorted = rendered.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 10);
+
+        let limited = arrangements(&fountains, &groups, EnumerationMode::InOrder).limit(3);
+        assert_eq!(limited.count(), 3);
+    }
+
+    #[test]
+    fn test_arrangements_are_valid() {
+        let example_lines = [
+            "???.### 1,1,3",
+            ".??..??...?##. 1,1,3",
+            "?#?#?#?#?#?#?#? 1,3,1,6",
+            "????.######..#####. 1,6,5",
+            "#.# 1",
+        ];
+        for line in example_lines {
+            let (fountains, groups) = parse_fountain_line(line);
+            let all: Vec<Fountains> =
+                arrangements(&fountains, &groups, EnumerationMode::InOrder).collect();
+            assert_eq!(all.len() as Num, get_possibility_count(&fountains, &groups));
+            for arrangement in all.iter() {
+                assert!(is_arrangement_valid(arrangement, &groups));
+                // Known fountains are kept as they were
+                for (known, chosen) in fountains.iter().zip(arrangement.iter()) {
+                    assert!(*known == Unknown || known == chosen);
+                }
+            }
+            let samples = arrangements(&fountains, &groups, EnumerationMode::Sample { seed: 7 })
+                .limit(20)
+                .collect::<Vec<_>>();
+            assert_eq!(samples.len(), if all.is_empty() { 0 } else { 20 });
+            assert!(samples.iter().all(|sample| all.contains(sample)));
+        }
+    }
+
+    #[test]
+    fn test_unfold() {
+        let (fountains, groups) = parse_fountain_line(".# 1");
+        let (unfolded_fountains, unfolded_groups) = unfold(&fountains, &groups, 5, Unknown);
+        assert_eq!(render_fountains(&unfolded_fountains), ".#?.#?.#?.#?.#");
+        assert_eq!(unfolded_groups, vec![1, 1, 1, 1, 1]);
+        let (unfolded_fountains, unfolded_groups) = unfold(&fountains, &groups, 2, Good);
+        assert_eq!(render_fountains(&unfolded_fountains), ".#..#");
+        assert_eq!(unfolded_groups, vec![1, 1]);
+        let (unfolded_fountains, unfolded_groups) = unfold(&fountains, &groups, 0, Good);
+        assert!(unfolded_fountains.is_empty() && unfolded_groups.is_empty());
+    }
+
+    #[test]
+    fn test_unfolded_possibility_count() {
+        let (fountains, groups) = parse_fountain_line("?###???????? 3,2,1");
+        assert_eq!(
+            unfolded_possibility_count(&fountains, &groups, 1, Unknown),
+            Ok(10)
+        );
+        assert_eq!(
+            unfolded_possibility_count(&fountains, &groups, 5, Unknown),
+            Ok(506250)
+        );
+        let (fountains, groups) = parse_fountain_line("?????????? 1");
+        assert!(unfolded_possibility_count(&fountains, &groups, 100, Unknown).is_err());
+    }
+
+    /// Whether concrete group sizes satisfy the specs, checked by plain recursion.
+    fn sizes_match_specs(sizes: &[u8], specs: &[GroupSpec]) -> bool {
+        match (specs.first(), sizes.first()) {
+            (None, _) => sizes.is_empty(),
+            (Some(GroupSpec::Wildcard), _) => {
+                sizes_match_specs(sizes, &specs[1..])
+                    || (!sizes.is_empty() && sizes_match_specs(&sizes[1..], specs))
+            }
+            (Some(GroupSpec::Sized { min, max, optional }), maybe_size) => {
+                let matches_here = maybe_size.is_some_and(|size| (*min..=*max).contains(size))
+                    && sizes_match_specs(&sizes[1..], &specs[1..]);
+                matches_here || (*optional && sizes_match_specs(sizes, &specs[1..]))
+            }
+        }
+    }
+
+    fn group_sizes(arrangement: &[Fountain]) -> Vec<u8> {
+        arrangement
+            .split(|fountain| *fountain == Good)
+            .filter(|run| !run.is_empty())
+            .map(|run| run.len() as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_flexible_line() {
+        let (fountains, specs) = parse_flexible_line("?#. 1-3,2?,*,4").unwrap();
+        assert_eq!(fountains, vec![Unknown, Broken, Good]);
+        assert_eq!(
+            specs,
+            vec![
+                GroupSpec::Sized {
+                    min: 1,
+                    max: 3,
+                    optional: false
+                },
+                GroupSpec::Sized {
+                    min: 2,
+                    max: 2,
+                    optional: true
+                },
+                GroupSpec::Wildcard,
+                GroupSpec::Sized {
+                    min: 4,
+                    max: 4,
+                    optional: false
+                },
+            ]
+        );
+        assert!(parse_flexible_line("?#. 3-1").is_err());
+        assert!(parse_flexible_line("?#. 0").is_err());
+        assert!(parse_flexible_line("?#x 1").is_err());
+        assert!(parse_flexible_line("?#.").is_err());
+    }
+
+    #[test]
+    fn test_automaton_exact_specs() {
+        let example_pairs: Vec<(&str, Num)> = vec![
+            ("???.### 1,1,3", 1),
+            (".??..??...?##. 1,1,3", 4),
+            ("?#?#?#?#?#?#?#? 1,3,1,6", 1),
+            ("????.#...#... 4,1,1", 1),
+            ("????.######..#####. 1,6,5", 4),
+            ("?###???????? 3,2,1", 10),
+            ("#.# 1", 0),
+        ];
+        for (line, expected) in example_pairs {
+            let (fountains, specs) = parse_flexible_line(line).unwrap();
+            let automaton = GroupAutomaton::compile(&specs);
+            assert_eq!(automaton.count_matches(&fountains), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_automaton_matches_brute_force() {
+        let spec_lines = [
+            "1-3",
+            "2?,1",
+            "*",
+            "1,*,1",
+            "1-2?,2-3?",
+            "*,3,*",
+            "1?,1?,1?",
+        ];
+        let mut next_random = lcg(38);
+        for spec_line in spec_lines {
+            let specs: Vec<GroupSpec> = spec_line
+                .split(',')
+                .map(|spec| GroupSpec::parse(spec).unwrap())
+                .collect();
+            let automaton = GroupAutomaton::compile(&specs);
+            for _ in 0..40 {
+                let fountains: Fountains = (0..next_random(10) + 1)
+                    .map(|_| [Good, Broken, Unknown, Unknown][next_random(4) as usize])
+                    .collect();
+                let unknown_indices: Vec<usize> = (0..fountains.len())
+                    .filter(|idx| fountains[*idx] == Unknown)
+                    .collect();
+                let mut expected = 0;
+                for mask in 0..1u32 << unknown_indices.len() {
+                    let mut arrangement = fountains.clone();
+                    for (bit, idx) in unknown_indices.iter().enumerate() {
+                        arrangement[*idx] = if mask & (1 << bit) != 0 { Broken } else { Good };
+                    }
+                    let is_match = sizes_match_specs(&group_sizes(&arrangement), &specs);
+                    assert_eq!(automaton.matches(&arrangement), is_match);
+                    expected += is_match as Num;
+                }
+                assert_eq!(automaton.count_matches(&fountains), Some(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_possibility_count_overflow() {
+        // Far more arrangements than fit in a u128
+        let fountains = vec![Unknown; 400];
+        let groups = vec![1; 60];
+        assert_eq!(checked_possibility_count(&fountains, &groups), None);
+    }
+
     #[test]
     fn test_is_arrangement_valid() {
         let example_pairs: Vec<(&str, bool)> = vec![
@@ -214,6 +951,7 @@ mod test {
             ("..##..##.. 2, 3", false),
             ("..##.. 2, 1", false),
             ("..##.. 2, 3", false),
+            ("#.# 1", false),
         ];
         for (input_line, expected) in example_pairs.iter() {
             let input = parse_fountain_line(&input_line);
@@ -1,19 +1,77 @@
-use std::collections::HashMap;
+type Num = u32;
+/// A cell in a pattern, as (row, col).
+pub type Pos = (usize, usize);
+type Mask = u128;
 
-use itertools::Itertools;
+/// A pattern of ash and rocks, stored as bitmasks along both axes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    rows: Vec<Mask>,
+    cols: Vec<Mask>,
+}
 
-use crate::zip_many::zip_many;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternError {
+    /// Row `row` of a pattern isn't as wide as the first row.
+    Ragged { row: usize },
+    /// A pattern is more than 128 cells across or down.
+    TooLarge,
+}
 
-type Num = u32;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Axis {
+    /// A mirror line between two rows.
+    Horizontal,
+    /// A mirror line between two columns.
+    Vertical,
+}
+
+/// A mirror line through a pattern, along with every pair of cells that doesn't match across it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reflection {
+    pub axis: Axis,
+    /// The number of rows above (or columns left of) the line.
+    pub position: usize,
+    /// Mismatched cells and their mirror images. Flipping either cell of a pair fixes it.
+    pub smudges: Vec<(Pos, Pos)>,
+}
 
-pub fn day13(lines: impl Iterator<Item = impl AsRef<str>>, accepted_error_count: u8) -> Num {
-    let mut sum = 0;
+impl Reflection {
+    pub fn error_count(&self) -> usize {
+        self.smudges.len()
+    }
+
+    pub fn score(&self) -> Num {
+        let position = self.position as Num;
+        match self.axis {
+            Axis::Horizontal => 100 * position,
+            Axis::Vertical => position,
+        }
+    }
+}
+
+pub fn day13(
+    lines: impl Iterator<Item = impl AsRef<str>>,
+    accepted_error_count: u8,
+) -> Result<Num, PatternError> {
+    Ok(analyze_patterns(lines, accepted_error_count as usize)?
+        .iter()
+        .map(|reflections| pattern_score(reflections, accepted_error_count as usize))
+        .sum())
+}
+
+/// Find the reflections of every blank-line separated pattern in the input.
+pub fn analyze_patterns(
+    lines: impl Iterator<Item = impl AsRef<str>>,
+    max_errors: usize,
+) -> Result<Vec<Vec<Reflection>>, PatternError> {
+    let mut patterns = Vec::new();
     let mut lines2 = lines.peekable();
     while lines2.peek().is_some() {
         let chunk = collect_chunk(&mut lines2);
-        sum += process_chunk(chunk, accepted_error_count);
+        patterns.push(find_reflections(&parse_pattern(&chunk)?, max_errors));
     }
-    sum
+    Ok(patterns)
 }
 
 fn collect_chunk<S: AsRef<str>>(lines: &mut impl Iterator<Item = S>) -> Vec<S> {
@@ -27,75 +85,133 @@ fn collect_chunk<S: AsRef<str>>(lines: &mut impl Iterator<Item = S>) -> Vec<S> {
     buffer
 }
 
-fn process_chunk(lines: Vec<impl AsRef<str>>, accepted_error_count: u8) -> Num {
-    let chars = lines
-        .iter()
-        .map(|x| x.as_ref().chars().collect_vec())
-        .collect_vec();
-    100 * find_horizontal_reflection(&chars, accepted_error_count).unwrap_or(0)
-        + find_vertical_reflection(&chars, accepted_error_count).unwrap_or(0)
+/// Read a pattern into bitmasks, one per row and one per column, where bit `n` is set for a `#`
+/// at index `n` along the line. Every row must be as wide as the first.
+pub fn parse_pattern(lines: &[impl AsRef<str>]) -> Result<Pattern, PatternError> {
+    let height = lines.len();
+    let width = lines
+        .first()
+        .map_or(0, |line| line.as_ref().chars().count());
+    if width > Mask::BITS as usize || height > Mask::BITS as usize {
+        return Err(PatternError::TooLarge);
+    }
+    let mut rows = vec![0; height];
+    let mut cols = vec![0; width];
+    for (row_idx, line) in lines.iter().enumerate() {
+        if line.as_ref().chars().count() != width {
+            return Err(PatternError::Ragged { row: row_idx });
+        }
+        for (col_idx, c) in line.as_ref().chars().enumerate() {
+            if c == '#' {
+                rows[row_idx] |= 1 << col_idx;
+                cols[col_idx] |= 1 << row_idx;
+            }
+        }
+    }
+    Ok(Pattern { rows, cols })
 }
 
-fn find_horizontal_reflection(chars: &Vec<Vec<char>>, accepted_error_count: u8) -> Option<Num> {
-    let transposed = zip_many(chars.iter().map(|x| x.iter().map(|x| x.clone()))).collect_vec();
-    find_vertical_reflection(&transposed, accepted_error_count)
+/// Score a pattern from the first horizontal and first vertical line with exactly `error_count` smudges.
+pub fn pattern_score(reflections: &[Reflection], error_count: usize) -> Num {
+    [Axis::Horizontal, Axis::Vertical]
+        .into_iter()
+        .filter_map(|axis| {
+            reflections
+                .iter()
+                .find(|r| r.axis == axis && r.error_count() == error_count)
+        })
+        .map(Reflection::score)
+        .sum()
 }
 
-fn find_vertical_reflection(chars: &Vec<Vec<char>>, accepted_error_count: u8) -> Option<Num> {
-    let reflections_per_row = chars.iter().map(|row| -> HashMap<usize, u8> {
-        let row_reflections = row
-            .windows(2)
-            .enumerate()
-            // Check for complete reflection (within this row only)
-            .filter_map(|(idx, _)| {
-                let mut row_errors = 0;
-                let mut left_idx = idx;
-                let mut right_idx = idx + 1;
-                while right_idx < row.len() {
-                    if row[left_idx] != row[right_idx] {
-                        row_errors += 1;
-                    }
-                    if row_errors > accepted_error_count {
-                        return None;
-                    }
-                    if left_idx == 0 {
-                        // Prevent underflow
-                        break;
-                    }
-                    left_idx -= 1;
-                    right_idx += 1;
-                }
-                return Some((idx, row_errors));
+/// Every mirror line with at most `max_errors` smudges.
+/// Horizontal lines come first, and each axis is in order of position.
+pub fn find_reflections(pattern: &Pattern, max_errors: usize) -> Vec<Reflection> {
+    let horizontal = (1..pattern.rows.len()).map(|position| (Axis::Horizontal, position));
+    let vertical = (1..pattern.cols.len()).map(|position| (Axis::Vertical, position));
+    horizontal
+        .chain(vertical)
+        .filter_map(|(axis, position)| {
+            let smudges = find_smudges(pattern, axis, position, max_errors)?;
+            Some(Reflection {
+                axis,
+                position,
+                smudges,
             })
-            .collect();
-        row_reflections
-    });
-    let reflection_set = reflections_per_row
-        .reduce(|row1, row2| {
-            let accumulated_errors = row1
-                .into_iter()
-                .filter_map(|(idx, errors1)| {
-                    row2.get(&idx)
-                        .map(|errors2| {
-                            let errors = errors1 + *errors2;
-                            (idx, errors)
-                        })
-                        .filter(|(_, errors)| *errors <= accepted_error_count)
-                })
-                .collect();
-            accumulated_errors
         })
-        .unwrap_or_default();
+        .collect()
+}
 
-    reflection_set
-        .iter()
-        .filter_map(|(idx, errors)| (*errors == accepted_error_count).then_some((*idx + 1) as Num))
-        .next()
+/// The mismatched cell pairs across a line, or `None` once there are more than `max_errors`.
+/// Each set bit in the XOR of two mirrored lines is one mismatch.
+fn find_smudges(
+    pattern: &Pattern,
+    axis: Axis,
+    position: usize,
+    max_errors: usize,
+) -> Option<Vec<(Pos, Pos)>> {
+    let lines = match axis {
+        Axis::Horizontal => &pattern.rows,
+        Axis::Vertical => &pattern.cols,
+    };
+    let mut smudges = Vec::new();
+    for offset in 0..position.min(lines.len() - position) {
+        let (near, far) = (position - 1 - offset, position + offset);
+        let mut diff = lines[near] ^ lines[far];
+        if smudges.len() + diff.count_ones() as usize > max_errors {
+            return None;
+        }
+        while diff != 0 {
+            let idx = diff.trailing_zeros() as usize;
+            diff &= diff - 1;
+            smudges.push(match axis {
+                Axis::Horizontal => ((near, idx), (far, idx)),
+                Axis::Vertical => ((idx, near), (idx, far)),
+            });
+        }
+    }
+    Some(smudges)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::lcg::lcg;
+    use itertools::Itertools;
+
+    /// Cell by cell comparison over the characters, to check the bitmasks against.
+    fn find_reflections_by_chars(lines: &[String], max_errors: usize) -> Vec<Reflection> {
+        let chars = lines.iter().map(|x| x.chars().collect_vec()).collect_vec();
+        let (height, width) = (chars.len(), chars[0].len());
+        let horizontal = (1..height).map(|position| (Axis::Horizontal, position, height, width));
+        let vertical = (1..width).map(|position| (Axis::Vertical, position, width, height));
+        horizontal
+            .chain(vertical)
+            .filter_map(|(axis, position, length, across)| {
+                let mut smudges = Vec::new();
+                for offset in 0..position.min(length - position) {
+                    for idx in 0..across {
+                        let (near, far) = match axis {
+                            Axis::Horizontal => {
+                                ((position - 1 - offset, idx), (position + offset, idx))
+                            }
+                            Axis::Vertical => {
+                                ((idx, position - 1 - offset), (idx, position + offset))
+                            }
+                        };
+                        if chars[near.0][near.1] != chars[far.0][far.1] {
+                            smudges.push((near, far));
+                        }
+                    }
+                }
+                (smudges.len() <= max_errors).then_some(Reflection {
+                    axis,
+                    position,
+                    smudges,
+                })
+            })
+            .collect()
+    }
 
     const EXAMPLE: &[&str] = &[
         "#.##..##.",
@@ -118,14 +234,135 @@ mod test {
     #[test]
     fn test_example() {
         let input = EXAMPLE;
-        let result = day13(input.into_iter(), 0);
+        let result = day13(input.into_iter(), 0).unwrap();
         assert_eq!(result, 405);
     }
 
     #[test]
     fn test_example_b() {
         let input = EXAMPLE;
-        let result = day13(input.into_iter(), 1);
+        let result = day13(input.into_iter(), 1).unwrap();
         assert_eq!(result, 400);
     }
+
+    #[test]
+    fn test_smudges() {
+        let patterns = analyze_patterns(EXAMPLE.iter(), 1).unwrap();
+        let smudged = |reflections: &Vec<Reflection>| {
+            reflections
+                .iter()
+                .filter(|r| r.error_count() == 1)
+                .cloned()
+                .collect_vec()
+        };
+        assert_eq!(
+            smudged(&patterns[0]),
+            vec![Reflection {
+                axis: Axis::Horizontal,
+                position: 3,
+                smudges: vec![((0, 0), (5, 0))],
+            }]
+        );
+        assert_eq!(
+            smudged(&patterns[1]),
+            vec![Reflection {
+                axis: Axis::Horizontal,
+                position: 1,
+                smudges: vec![((0, 4), (1, 4))],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_pattern_errors() {
+        assert_eq!(
+            parse_pattern(&["#.#", "#.#.#", "#.#"]),
+            Err(PatternError::Ragged { row: 1 })
+        );
+        assert_eq!(
+            parse_pattern(&["#.#", "#."]),
+            Err(PatternError::Ragged { row: 1 })
+        );
+        let wide = "#".repeat(129);
+        assert_eq!(parse_pattern(&[&wide]), Err(PatternError::TooLarge));
+        let tall = vec!["#"; 129];
+        assert_eq!(parse_pattern(&tall), Err(PatternError::TooLarge));
+        assert!(parse_pattern(&["#".repeat(128)]).is_ok());
+        assert_eq!(
+            day13(["##", "#.#"].iter(), 0),
+            Err(PatternError::Ragged { row: 1 })
+        );
+    }
+
+    #[test]
+    fn test_every_axis() {
+        let pattern = parse_pattern(&["####", "#..#"]).unwrap();
+        let reflections = find_reflections(&pattern, 0);
+        let axes = reflections
+            .iter()
+            .map(|r| (r.axis, r.position))
+            .collect_vec();
+        assert_eq!(axes, vec![(Axis::Vertical, 2)]);
+
+        let pattern = parse_pattern(&["###", "###"]).unwrap();
+        let reflections = find_reflections(&pattern, 0);
+        let axes = reflections
+            .iter()
+            .map(|r| (r.axis, r.position))
+            .collect_vec();
+        assert_eq!(
+            axes,
+            vec![
+                (Axis::Horizontal, 1),
+                (Axis::Vertical, 1),
+                (Axis::Vertical, 2)
+            ]
+        );
+        // The first line along each axis counts.
+        assert_eq!(pattern_score(&reflections, 0), 101);
+    }
+
+    #[test]
+    fn test_matches_char_comparison() {
+        let mut next = lcg(13);
+        let mut patterns = vec![
+            EXAMPLE[..7].iter().map(|x| x.to_string()).collect_vec(),
+            EXAMPLE[8..].iter().map(|x| x.to_string()).collect_vec(),
+        ];
+        for _ in 0..200 {
+            let (height, width) = (1 + next(8) as usize, 1 + next(8) as usize);
+            // Mostly mirrored halves, so there are reflections to find.
+            let half: Vec<String> = (0..height)
+                .map(|_| {
+                    (0..width)
+                        .map(|_| if next(2) == 0 { '#' } else { '.' })
+                        .collect()
+                })
+                .collect_vec();
+            let mut lines = half.iter().chain(half.iter().rev()).cloned().collect_vec();
+            for _ in 0..next(3) {
+                let (row, col) = (
+                    next(lines.len() as u64) as usize,
+                    next(width as u64) as usize,
+                );
+                let flipped = if lines[row].as_bytes()[col] == b'#' {
+                    "."
+                } else {
+                    "#"
+                };
+                lines[row].replace_range(col..col + 1, flipped);
+            }
+            patterns.push(lines);
+        }
+        for lines in patterns {
+            for max_errors in 0..3 {
+                assert_eq!(
+                    find_reflections(&parse_pattern(&lines).unwrap(), max_errors),
+                    find_reflections_by_chars(&lines, max_errors),
+                    "{:?}",
+                    lines
+                );
+            }
+        }
+    }
 }
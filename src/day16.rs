@@ -1,5 +1,10 @@
 use crate::cursor_grid::{Direction::*, *};
-use std::collections::HashSet;
+use crate::parallel::par_map;
+use crate::small_set::SmallSet;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
 
 pub type Num = u32;
 pub type Node = Option<MirrorSplitter>;
@@ -70,14 +75,125 @@ impl MirrorSplitter {
     }
 }
 
-pub fn route_light(node: Node, dir: Direction) -> &'static [Direction] {
-    let dir_static = match dir {
-        Up => &[Up],
-        Down => &[Down],
-        Left => &[Left],
-        Right => &[Right],
-    };
-    node.map_or(dir_static, |item| item.route_light(dir))
+/// How a tile redirects light entering it.
+pub trait OpticalComponent: Debug + Send + Sync {
+    fn route(&self, incoming: Direction) -> SmallSet<Direction>;
+}
+
+impl OpticalComponent for MirrorSplitter {
+    fn route(&self, incoming: Direction) -> SmallSet<Direction> {
+        self.route_light(incoming).iter().copied().collect()
+    }
+}
+
+impl<C: OpticalComponent + ?Sized> OpticalComponent for Arc<C> {
+    fn route(&self, incoming: Direction) -> SmallSet<Direction> {
+        self.as_ref().route(incoming)
+    }
+}
+
+/// Swallows all light.
+#[derive(Debug, Clone, Copy)]
+pub struct Absorber;
+
+impl OpticalComponent for Absorber {
+    fn route(&self, _incoming: Direction) -> SmallSet<Direction> {
+        SmallSet::new()
+    }
+}
+
+/// Lets light through in one direction only, absorbing it otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct OneWayPrism(pub Direction);
+
+impl OpticalComponent for OneWayPrism {
+    fn route(&self, incoming: Direction) -> SmallSet<Direction> {
+        let mut outgoing = SmallSet::new();
+        if incoming == self.0 {
+            outgoing.insert(incoming);
+        }
+        outgoing
+    }
+}
+
+/// Sends light out in all four directions, whichever way it came in.
+#[derive(Debug, Clone, Copy)]
+pub struct FourWaySplitter;
+
+impl OpticalComponent for FourWaySplitter {
+    fn route(&self, _incoming: Direction) -> SmallSet<Direction> {
+        [Up, Down, Left, Right].into_iter().collect()
+    }
+}
+
+/// Empty tiles let light pass straight through.
+pub fn route_light<C: OpticalComponent>(node: &Option<C>, dir: Direction) -> SmallSet<Direction> {
+    match node {
+        Some(component) => component.route(dir),
+        None => [dir].into_iter().collect(),
+    }
+}
+
+pub type DynNode = Option<Arc<dyn OpticalComponent>>;
+
+/// Which component each character of a contraption stands for.
+pub struct ComponentSet {
+    components: HashMap<char, Arc<dyn OpticalComponent>>,
+}
+
+impl ComponentSet {
+    /// The mirrors and splitters from the puzzle.
+    pub fn standard() -> Self {
+        let components = ['/', '\\', '|', '-']
+            .into_iter()
+            .map(|c| -> (char, Arc<dyn OpticalComponent>) {
+                (c, Arc::new(MirrorSplitter::from_char(&c).unwrap()))
+            })
+            .collect();
+        Self { components }
+    }
+
+    pub fn with(mut self, c: char, component: impl OpticalComponent + 'static) -> Self {
+        self.components.insert(c, Arc::new(component));
+        self
+    }
+
+    /// Add a component by name.
+    /// Known names are `absorber`, `four-way`, and `one-way-up`/`-down`/`-left`/`-right`.
+    pub fn with_named(self, c: char, name: &str) -> Result<Self, &'static str> {
+        Ok(match name {
+            "absorber" => self.with(c, Absorber),
+            "four-way" => self.with(c, FourWaySplitter),
+            "one-way-up" => self.with(c, OneWayPrism(Up)),
+            "one-way-down" => self.with(c, OneWayPrism(Down)),
+            "one-way-left" => self.with(c, OneWayPrism(Left)),
+            "one-way-right" => self.with(c, OneWayPrism(Right)),
+            _ => return Err("Unknown component name"),
+        })
+    }
+
+    /// Add a component from a `c=name` spec, e.g. `#=absorber`.
+    pub fn with_spec(self, spec: &str) -> Result<Self, &'static str> {
+        let (c, name) = spec.split_once('=').ok_or("Spec must look like c=name")?;
+        let mut chars = c.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => self.with_named(c, name),
+            _ => Err("Spec must name exactly one character"),
+        }
+    }
+
+    pub fn parse_grid(&self, lines: impl Iterator<Item = impl AsRef<str>>) -> Grid<DynNode> {
+        let mut grid = Grid::new();
+        for line in lines {
+            let row = line
+                .as_ref()
+                .chars()
+                .map(|c| self.components.get(&c).cloned())
+                .collect();
+            grid.rows.push(row);
+        }
+        grid
+    }
 }
 
 pub fn parse_grid(lines: impl Iterator<Item = impl AsRef<str>>) -> Grid<Node> {
@@ -98,35 +214,495 @@ pub fn parse_row(line: &str) -> Row {
     row
 }
 
-pub fn count_energized_tiles(start_cursor: Cursor, grid: &Grid<Node>) -> Num {
-    let mut cursors = vec![start_cursor];
-    let mut energy_map: HashSet<Coord> = HashSet::new();
+/// Everything learned from following a beam until it leaves the grid or repeats itself.
+#[derive(Debug, Default)]
+pub struct BeamTrace {
+    pub energized: HashSet<Coord>,
+    /// Number of distinct beam directions that passed through each tile.
+    pub beam_counts: HashMap<Coord, Num>,
+    /// Each loop the beam got caught in, as the cursors making up the cycle.
+    pub loops: Vec<Vec<Cursor>>,
+}
+
+pub fn trace_beams<C: OpticalComponent>(start_cursor: Cursor, grid: &Grid<Option<C>>) -> BeamTrace {
+    let mut trace = BeamTrace::default();
+    if !grid.is_within_bounds(start_cursor.0) {
+        return trace;
+    }
+    let mut visited: HashSet<Cursor> = HashSet::new();
+    // Depth-first search over cursors. A successor still on the current path closes a loop.
+    let mut path: Vec<(Cursor, Vec<Cursor>)> = Vec::new();
+    let mut path_indices: HashMap<Cursor, usize> = HashMap::new();
+    let mut to_enter = Some(start_cursor);
     loop {
-        cursors = cursors
-            .into_iter()
-            .flat_map(|(pos, dir): Cursor| {
-                let node = grid.get(pos);
-                let is_energized_splitter = match node {
-                    Some(MirrorSplitter::Splitter(_)) => energy_map.contains(&pos),
-                    _ => false,
-                };
-                energy_map.insert(pos);
-                if is_energized_splitter {
-                    vec![]
-                } else {
-                    next_cursors(&(pos, dir), node)
-                }
-            })
-            .filter(|cursor| grid.is_within_bounds(cursor.0))
-            .collect();
-        if cursors.is_empty() {
+        if let Some(cursor) = to_enter.take() {
+            visited.insert(cursor);
+            trace.energized.insert(cursor.0);
+            *trace.beam_counts.entry(cursor.0).or_default() += 1;
+            let successors = next_cursors(&cursor, grid.get(cursor.0))
+                .into_iter()
+                .filter(|next| grid.is_within_bounds(next.0))
+                .collect();
+            path_indices.insert(cursor, path.len());
+            path.push((cursor, successors));
+        }
+        let Some((cursor, successors)) = path.last_mut() else {
             break;
+        };
+        match successors.pop() {
+            Some(next) => {
+                if let Some(loop_start) = path_indices.get(&next) {
+                    let cycle = path[*loop_start..].iter().map(|(c, _)| *c).collect();
+                    trace.loops.push(cycle);
+                } else if !visited.contains(&next) {
+                    to_enter = Some(next);
+                }
+            }
+            None => {
+                path_indices.remove(cursor);
+                path.pop();
+            }
+        }
+    }
+    trace
+}
+
+pub fn count_energized_tiles<C: OpticalComponent>(
+    start_cursor: Cursor,
+    grid: &Grid<Option<C>>,
+) -> Num {
+    trace_beams(start_cursor, grid).energized.len() as Num
+}
+
+fn next_cursors<C: OpticalComponent>(cursor: &Cursor, node: &Option<C>) -> Vec<Cursor> {
+    let next_dirs = route_light(node, cursor.1);
+    next_dirs.iter().map(|d| (cursor.0 + d, d)).collect()
+}
+
+/// Every edge entry cursor with the number of tiles it energizes, best first.
+/// Ties keep the order of `Grid::edge_cursors`. The cursors are spread over `workers` threads.
+pub fn rank_entry_cursors<C: OpticalComponent>(
+    grid: &Grid<Option<C>>,
+    workers: usize,
+) -> Vec<(Cursor, Num)> {
+    let splitter_graph = SplitterGraph::new(grid);
+    let mut ranked: Vec<(Cursor, Num)> = par_map(grid.edge_cursors(), workers, |cursor| {
+        (cursor, splitter_graph.count_energized_tiles(cursor, grid))
+    });
+    ranked.sort_by_key(|(_, count)| Reverse(*count));
+    ranked
+}
+
+/// Draw the grid with energized tiles as `#` and the rest as `.`.
+pub fn render_energized<T>(grid: &Grid<T>, energized: &HashSet<Coord>) -> String {
+    (0..grid.rows.len() as i32)
+        .map(|row_idx| {
+            (0..grid.rows[0].len() as i32)
+                .map(|col_idx| {
+                    if energized.contains(&(row_idx, col_idx)) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Tiles of a grid, stored as a bitset.
+#[derive(Debug, Clone)]
+struct TileSet {
+    width: usize,
+    bits: Vec<u64>,
+}
+
+impl TileSet {
+    fn new<T>(grid: &Grid<T>) -> Self {
+        let width = grid.rows[0].len();
+        let tile_count = grid.rows.len() * width;
+        Self {
+            width,
+            bits: vec![0; tile_count.div_ceil(64)],
         }
     }
-    return energy_map.len() as Num;
+
+    fn insert(&mut self, pos: Coord) {
+        let idx = pos.0 as usize * self.width + pos.1 as usize;
+        self.bits[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn union_with(&mut self, other: &Self) {
+        for (bits, other_bits) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *bits |= other_bits;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+}
+
+/// Follow a single beam until it reaches a component that splits it, leaves the grid, is
+/// absorbed, or repeats.
+/// Returns the lit tiles (including the splitter) and the cursor entering the splitter, if any.
+fn trace_segment<C: OpticalComponent>(
+    start_cursor: Cursor,
+    grid: &Grid<Option<C>>,
+) -> (TileSet, Option<Cursor>) {
+    let mut tiles = TileSet::new(grid);
+    let mut visited: HashSet<Cursor> = HashSet::new();
+    let mut cursor = start_cursor;
+    while grid.is_within_bounds(cursor.0) && visited.insert(cursor) {
+        tiles.insert(cursor.0);
+        let next = next_cursors(&cursor, grid.get(cursor.0));
+        match next.len() {
+            0 => break,
+            1 => cursor = next[0],
+            _ => return (tiles, Some(cursor)),
+        }
+    }
+    (tiles, None)
 }
 
-fn next_cursors(cursor: &Cursor, node: &Node) -> Vec<Cursor> {
-    let next_dirs = route_light(*node, cursor.1);
-    next_dirs.iter().map(|d| (cursor.0 + *d, *d)).collect()
+/// Beam segments between splitters, precomputed so that many start cursors can be answered
+/// without re-simulating the shared parts of their paths.
+///
+/// Each cursor entering a splitting component is a node lighting the tiles of its outgoing
+/// segments, with edges to the splitters those segments end at. Cycles are collapsed into
+/// strongly connected components, and each component stores every tile lit from it onwards.
+pub struct SplitterGraph {
+    components: HashMap<Cursor, usize>,
+    reachable_tiles: Vec<TileSet>,
+}
+
+impl SplitterGraph {
+    pub fn new<C: OpticalComponent>(grid: &Grid<Option<C>>) -> Self {
+        let mut node_tiles: HashMap<Cursor, TileSet> = HashMap::new();
+        let mut edges: HashMap<Cursor, Vec<Cursor>> = HashMap::new();
+        for pos in grid.positions() {
+            for incoming in [Up, Down, Left, Right] {
+                let node = (pos, incoming);
+                let outgoing = next_cursors(&node, grid.get(pos));
+                if outgoing.len() < 2 {
+                    continue;
+                }
+                let mut tiles = TileSet::new(grid);
+                tiles.insert(pos);
+                let mut targets = Vec::new();
+                for next in outgoing {
+                    let (segment_tiles, maybe_end) = trace_segment(next, grid);
+                    tiles.union_with(&segment_tiles);
+                    targets.extend(maybe_end);
+                }
+                node_tiles.insert(node, tiles);
+                edges.insert(node, targets);
+            }
+        }
+
+        let mut tarjan = Tarjan {
+            edges: &edges,
+            index_counter: 0,
+            indices: HashMap::new(),
+            low_links: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: HashMap::new(),
+            component_members: Vec::new(),
+        };
+        for pos in grid.positions() {
+            for incoming in [Up, Down, Left, Right] {
+                let node = (pos, incoming);
+                if edges.contains_key(&node) && !tarjan.indices.contains_key(&node) {
+                    tarjan.visit(node);
+                }
+            }
+        }
+
+        // Tarjan emits components in reverse topological order, so successors are always done first.
+        let mut reachable_tiles: Vec<TileSet> = Vec::new();
+        for members in tarjan.component_members.iter() {
+            let mut tiles = TileSet::new(grid);
+            for member in members {
+                tiles.union_with(&node_tiles[member]);
+                for target in edges[member].iter() {
+                    let target_component = tarjan.components[target];
+                    if target_component < reachable_tiles.len() {
+                        tiles.union_with(&reachable_tiles[target_component]);
+                    }
+                }
+            }
+            reachable_tiles.push(tiles);
+        }
+
+        Self {
+            components: tarjan.components,
+            reachable_tiles,
+        }
+    }
+
+    /// Same result as `count_energized_tiles`, using the precomputed segments.
+    pub fn count_energized_tiles<C: OpticalComponent>(
+        &self,
+        start_cursor: Cursor,
+        grid: &Grid<Option<C>>,
+    ) -> Num {
+        let (mut tiles, maybe_end) = trace_segment(start_cursor, grid);
+        if let Some(splitter_cursor) = maybe_end {
+            tiles.union_with(&self.reachable_tiles[self.components[&splitter_cursor]]);
+        }
+        tiles.len() as Num
+    }
+}
+
+/// Tarjan's strongly connected components algorithm over the splitter graph.
+struct Tarjan<'a> {
+    edges: &'a HashMap<Cursor, Vec<Cursor>>,
+    index_counter: usize,
+    indices: HashMap<Cursor, usize>,
+    low_links: HashMap<Cursor, usize>,
+    stack: Vec<Cursor>,
+    on_stack: HashSet<Cursor>,
+    components: HashMap<Cursor, usize>,
+    component_members: Vec<Vec<Cursor>>,
+}
+
+impl<'a> Tarjan<'a> {
+    /// Walks depth first with an explicit stack of (node, next edge to follow), since chains of
+    /// splitters can be far longer than a thread's stack allows for recursion.
+    fn visit(&mut self, root: Cursor) {
+        self.open(root);
+        let mut call_stack = vec![(root, 0)];
+        while let Some((node, edge_idx)) = call_stack.pop() {
+            if let Some(target) = self.edges[&node].get(edge_idx) {
+                call_stack.push((node, edge_idx + 1));
+                if !self.indices.contains_key(target) {
+                    self.open(*target);
+                    call_stack.push((*target, 0));
+                } else if self.on_stack.contains(target) {
+                    let low_link = self.low_links[&node].min(self.indices[target]);
+                    self.low_links.insert(node, low_link);
+                }
+                continue;
+            }
+
+            // Every edge is done, so pass the low link back up to the caller.
+            if let Some((parent, _)) = call_stack.last() {
+                let low_link = self.low_links[parent].min(self.low_links[&node]);
+                self.low_links.insert(*parent, low_link);
+            }
+            if self.low_links[&node] == self.indices[&node] {
+                self.close_component(node);
+            }
+        }
+    }
+
+    fn open(&mut self, node: Cursor) {
+        self.indices.insert(node, self.index_counter);
+        self.low_links.insert(node, self.index_counter);
+        self.index_counter += 1;
+        self.stack.push(node);
+        self.on_stack.insert(node);
+    }
+
+    /// Pop everything above and including `root` off the stack as one component.
+    fn close_component(&mut self, root: Cursor) {
+        let component_idx = self.component_members.len();
+        let mut members = Vec::new();
+        loop {
+            let member = self.stack.pop().unwrap();
+            self.on_stack.remove(&member);
+            self.components.insert(member, component_idx);
+            members.push(member);
+            if member == root {
+                break;
+            }
+        }
+        self.component_members.push(members);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &[&str] = &[
+        r".|...\....",
+        r"|.-.\.....",
+        r".....|-...",
+        r"........|.",
+        r"..........",
+        r".........\",
+        r"..../.\\..",
+        r".-.-/..|..",
+        r".|....-|.\",
+        r"..//.|....",
+    ];
+
+    #[test]
+    fn test_example() {
+        let grid = parse_grid(EXAMPLE.iter());
+        assert_eq!(count_energized_tiles(((0, 0), Right), &grid), 46);
+    }
+
+    #[test]
+    fn test_rank_entry_cursors() {
+        let grid = parse_grid(EXAMPLE.iter());
+        let ranked = rank_entry_cursors(&grid, 1);
+        assert_eq!(rank_entry_cursors(&grid, 4), ranked);
+        assert_eq!(ranked.len(), 40);
+        assert_eq!(ranked[0], (((0, 3), Down), 51));
+        assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    #[test]
+    fn test_render_energized() {
+        let grid = parse_grid(EXAMPLE.iter());
+        let trace = trace_beams(((0, 0), Right), &grid);
+        let expected = [
+            "######....",
+            ".#...#....",
+            ".#...#####",
+            ".#...##...",
+            ".#...##...",
+            ".#...##...",
+            ".#..####..",
+            "########..",
+            ".#######..",
+            ".#...#.#..",
+        ];
+        assert_eq!(
+            render_energized(&grid, &trace.energized),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_splitter_graph_matches_simulation() {
+        let grids = [
+            parse_grid(EXAMPLE.iter()),
+            parse_grid([r"/.\", r"...", r"\./"].iter()),
+            // Splitters feeding each other in a cycle
+            parse_grid([r"|.-.\", r".....", r"-.|./", r"\...-"].iter()),
+        ];
+        for grid in grids.iter() {
+            let graph = SplitterGraph::new(grid);
+            let start_cursors = grid
+                .positions()
+                .flat_map(|pos| [Up, Down, Left, Right].map(|dir| (pos, dir)));
+            for start_cursor in start_cursors {
+                assert_eq!(
+                    graph.count_energized_tiles(start_cursor, grid),
+                    count_energized_tiles(start_cursor, grid),
+                    "{:?}",
+                    start_cursor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_component_set() {
+        let standard_grid = ComponentSet::standard().parse_grid(EXAMPLE.iter());
+        assert_eq!(count_energized_tiles(((0, 0), Right), &standard_grid), 46);
+        assert_eq!(rank_entry_cursors(&standard_grid, 1)[0].1, 51);
+        assert!(ComponentSet::standard().with_named('?', "mystery").is_err());
+        assert!(ComponentSet::standard().with_spec("#=absorber").is_ok());
+        assert!(ComponentSet::standard().with_spec("##=absorber").is_err());
+        assert!(ComponentSet::standard().with_spec("ab=prism").is_err());
+        assert!(ComponentSet::standard().with_spec("absorber").is_err());
+    }
+
+    #[test]
+    fn test_custom_components() {
+        let components = ComponentSet::standard()
+            .with('#', Absorber)
+            .with('+', FourWaySplitter)
+            .with('>', OneWayPrism(Right));
+        let grid = components.parse_grid([r"..#..", r"..>..", r".....", r"..+.."].iter());
+        // Absorbed
+        assert_eq!(count_energized_tiles(((0, 0), Right), &grid), 3);
+        // Passes the prism one way...
+        assert_eq!(count_energized_tiles(((1, 0), Right), &grid), 5);
+        // ...but not the other
+        assert_eq!(count_energized_tiles(((1, 4), Left), &grid), 3);
+        // Four-way splitter lights its row, and its column up to the prism
+        assert_eq!(count_energized_tiles(((3, 0), Right), &grid), 7);
+
+        let graph = SplitterGraph::new(&grid);
+        for start_cursor in grid.edge_cursors() {
+            assert_eq!(
+                graph.count_energized_tiles(start_cursor, &grid),
+                count_energized_tiles(start_cursor, &grid)
+            );
+        }
+    }
+
+    #[test]
+    fn test_mirror_loop_terminates() {
+        let grid = parse_grid([r"/.\", r"...", r"\./"].iter());
+        let trace = trace_beams(((0, 1), Right), &grid);
+        assert_eq!(trace.energized.len(), 8);
+        assert_eq!(trace.loops.len(), 1);
+        assert_eq!(trace.loops[0].len(), 8);
+        assert_eq!(trace.loops[0][0], ((0, 1), Right));
+        assert!(trace.beam_counts.values().all(|count| *count == 1));
+    }
+
+    #[test]
+    fn test_beam_counts() {
+        // The beam crosses the middle tile going right, then again going down.
+        let grid = parse_grid([r"./\", r"../", r"..."].iter());
+        let trace = trace_beams(((1, 0), Right), &grid);
+        assert_eq!(trace.beam_counts[&(1, 1)], 2);
+        assert_eq!(trace.beam_counts[&(1, 0)], 1);
+        assert!(trace.loops.is_empty());
+    }
+
+    #[test]
+    fn test_splitter_graph_long_cycle() {
+        // A row of `|` splitters, each sending its upward beam on to the next splitter and its
+        // downward beam back to itself, with the last one looping round to the first.
+        let splitter_count = 2000;
+        let width = 2 * splitter_count + 2;
+        let mut rows = vec![vec!['.'; width]; 4];
+        for idx in 0..splitter_count {
+            let col = 2 * idx + 1;
+            rows[0][col] = '/';
+            rows[0][col + 1] = '\\';
+            rows[1][col] = '|';
+            rows[1][col + 1] = '\\';
+            rows[2][col] = '\\';
+            rows[2][col + 1] = '/';
+        }
+        rows[1][0] = '/';
+        rows[1][width - 1] = '\\';
+        rows[3][0] = '\\';
+        rows[3][width - 1] = '/';
+        let lines: Vec<String> = rows.iter().map(|row| row.iter().collect()).collect();
+        let grid = parse_grid(lines.iter());
+
+        // A small stack, which recursing once per splitter would overflow.
+        let graph = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn({
+                let grid = grid.clone();
+                move || SplitterGraph::new(&grid)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(graph.components.len(), 2 * splitter_count);
+        assert_eq!(graph.reachable_tiles.len(), 1);
+        for start_cursor in [((1, 0), Right), ((3, 5), Left), ((0, 7), Down)] {
+            assert_eq!(
+                graph.count_energized_tiles(start_cursor, &grid),
+                count_energized_tiles(start_cursor, &grid)
+            );
+        }
+    }
 }
@@ -0,0 +1,134 @@
+use crate::cursor_grid::Coord;
+
+type Num = i64;
+
+/// Edges of a closed polygon, including the one from the last vertex back to the first.
+fn edges(vertices: &[Coord]) -> impl Iterator<Item = (Coord, Coord)> + '_ {
+    vertices
+        .iter()
+        .copied()
+        .zip(vertices.iter().copied().cycle().skip(1))
+}
+
+/// Twice the enclosed area (shoelace formula). Always an integer for lattice polygons.
+pub fn shoelace_double_area(vertices: &[Coord]) -> Num {
+    let signed_sum: Num = edges(vertices)
+        .map(|(a, b)| a.0 as Num * b.1 as Num - b.0 as Num * a.1 as Num)
+        .sum();
+    signed_sum.abs()
+}
+
+pub fn shoelace_area(vertices: &[Coord]) -> f64 {
+    shoelace_double_area(vertices) as f64 / 2.0
+}
+
+/// Euclidean length of the polygon's outline.
+pub fn perimeter(vertices: &[Coord]) -> f64 {
+    edges(vertices)
+        .map(|(a, b)| {
+            let d_row = (b.0 - a.0) as f64;
+            let d_col = (b.1 - a.1) as f64;
+            d_row.hypot(d_col)
+        })
+        .sum()
+}
+
+/// Number of lattice points lying on the polygon's outline.
+pub fn boundary_point_count(vertices: &[Coord]) -> Num {
+    edges(vertices)
+        .map(|(a, b)| gcd((b.0 - a.0).abs() as Num, (b.1 - a.1).abs() as Num))
+        .sum()
+}
+
+/// Number of lattice points strictly inside the polygon, via Pick's theorem:
+/// A = I + B/2 - 1, so 2I = 2A - B + 2.
+pub fn interior_point_count(vertices: &[Coord]) -> Num {
+    let double_area = shoelace_double_area(vertices);
+    let boundary = boundary_point_count(vertices);
+    (double_area - boundary + 2) / 2
+}
+
+/// Ray casting point-in-polygon test. Points on the outline count as outside.
+pub fn contains_point(vertices: &[Coord], point: Coord) -> bool {
+    if edges(vertices).any(|(a, b)| is_on_segment(point, a, b)) {
+        return false;
+    }
+    // Cast a ray from the point towards increasing columns and count edge crossings.
+    // Using a half-open row check means a vertex touching the ray is counted exactly once.
+    let (p_row, p_col) = (point.0 as f64, point.1 as f64);
+    let mut inside = false;
+    for (a, b) in edges(vertices) {
+        let (a_row, a_col) = (a.0 as f64, a.1 as f64);
+        let (b_row, b_col) = (b.0 as f64, b.1 as f64);
+        if (a_row > p_row) != (b_row > p_row) {
+            let crossing_col = a_col + (p_row - a_row) * (b_col - a_col) / (b_row - a_row);
+            if crossing_col > p_col {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn is_on_segment(point: Coord, a: Coord, b: Coord) -> bool {
+    let cross =
+        (b.0 - a.0) as Num * (point.1 - a.1) as Num - (b.1 - a.1) as Num * (point.0 - a.0) as Num;
+    cross == 0
+        && (a.0.min(b.0)..=a.0.max(b.0)).contains(&point.0)
+        && (a.1.min(b.1)..=a.1.max(b.1)).contains(&point.1)
+}
+
+fn gcd(a: Num, b: Num) -> Num {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SQUARE: &[Coord] = &[(0, 0), (0, 4), (4, 4), (4, 0)];
+    const TRIANGLE: &[Coord] = &[(0, 0), (0, 4), (4, 0)];
+
+    #[test]
+    fn test_area() {
+        assert_eq!(shoelace_double_area(SQUARE), 32);
+        assert_eq!(shoelace_area(SQUARE), 16.0);
+        assert_eq!(shoelace_area(TRIANGLE), 8.0);
+        // Winding order doesn't matter
+        let reversed: Vec<Coord> = SQUARE.iter().rev().copied().collect();
+        assert_eq!(shoelace_area(&reversed), 16.0);
+    }
+
+    #[test]
+    fn test_boundary_and_perimeter() {
+        assert_eq!(boundary_point_count(SQUARE), 16);
+        assert_eq!(perimeter(SQUARE), 16.0);
+        assert_eq!(boundary_point_count(TRIANGLE), 12);
+    }
+
+    #[test]
+    fn test_interior_point_count() {
+        assert_eq!(interior_point_count(SQUARE), 9);
+        assert_eq!(interior_point_count(TRIANGLE), 3);
+    }
+
+    #[test]
+    fn test_contains_point() {
+        assert!(contains_point(SQUARE, (2, 2)));
+        assert!(contains_point(SQUARE, (1, 3)));
+        assert!(!contains_point(SQUARE, (0, 2)));
+        assert!(!contains_point(SQUARE, (5, 2)));
+        assert!(contains_point(TRIANGLE, (1, 1)));
+        assert!(!contains_point(TRIANGLE, (3, 3)));
+        // Interior point count agrees with brute-force containment
+        let brute_force = (0..=4)
+            .flat_map(|row| (0..=4).map(move |col| (row, col)))
+            .filter(|point| contains_point(TRIANGLE, *point))
+            .count();
+        assert_eq!(brute_force as Num, interior_point_count(TRIANGLE));
+    }
+}
@@ -0,0 +1,15 @@
+/// One step of Knuth's MMIX linear congruential generator: cheap, deterministic pseudo-randomness.
+pub fn lcg_step(state: u64) -> u64 {
+    state
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407)
+}
+
+/// A seeded generator for randomized tests. Each call returns a number below its argument.
+#[cfg(test)]
+pub fn lcg(mut seed: u64) -> impl FnMut(u64) -> u64 {
+    move |modulus| {
+        seed = lcg_step(seed);
+        (seed >> 33) % modulus
+    }
+}
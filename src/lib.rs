@@ -11,5 +11,10 @@ pub mod day15;
 pub mod day16;
 
 pub mod cursor_grid;
+pub mod geometry;
+pub mod lcg;
 pub mod linked_list;
+pub mod nonogram;
+pub mod parallel;
+pub mod small_set;
 pub mod zip_many;
@@ -0,0 +1,202 @@
+use crate::cursor_grid::Grid;
+use crate::day12::{checked_possibility_count, render_fountains, Fountain, Fountains, Groups};
+use Fountain::*;
+
+/// A picross puzzle: group clues for every row and column.
+pub struct Nonogram {
+    pub row_clues: Vec<Groups>,
+    pub col_clues: Vec<Groups>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NonogramSolution {
+    Unique(Grid<Fountain>),
+    /// Two of the possible solutions.
+    Multiple(Grid<Fountain>, Grid<Fountain>),
+    None,
+}
+
+impl Nonogram {
+    pub fn new(row_clues: Vec<Groups>, col_clues: Vec<Groups>) -> Self {
+        Self {
+            row_clues,
+            col_clues,
+        }
+    }
+
+    pub fn solve(&self) -> NonogramSolution {
+        self.solve_counting_guesses().0
+    }
+
+    /// Solve, also returning how many cells had to be guessed along the way.
+    fn solve_counting_guesses(&self) -> (NonogramSolution, usize) {
+        let width = self.col_clues.len();
+        let grid = Grid {
+            rows: vec![vec![Unknown; width]; self.row_clues.len()],
+        };
+        let mut solutions = Vec::new();
+        let mut guesses = 0;
+        self.search(grid, &mut solutions, &mut guesses);
+        let mut solutions = solutions.into_iter();
+        let solution = match (solutions.next(), solutions.next()) {
+            (Some(first), Some(second)) => NonogramSolution::Multiple(first, second),
+            (Some(only), None) => NonogramSolution::Unique(only),
+            _ => NonogramSolution::None,
+        };
+        (solution, guesses)
+    }
+
+    /// Propagate forced cells, then guess on the first unknown cell when stuck.
+    /// Stops once two solutions have been found.
+    fn search(
+        &self,
+        mut grid: Grid<Fountain>,
+        solutions: &mut Vec<Grid<Fountain>>,
+        guesses: &mut usize,
+    ) {
+        if !self.propagate(&mut grid) {
+            return;
+        }
+        let Some(pos) = grid.positions().find(|pos| *grid.get(*pos) == Unknown) else {
+            solutions.push(grid);
+            return;
+        };
+        for guess in [Broken, Good] {
+            if solutions.len() >= 2 {
+                return;
+            }
+            let mut guessed_grid = grid.clone();
+            guessed_grid.set(pos, guess);
+            *guesses += 1;
+            self.search(guessed_grid, solutions, guesses);
+        }
+    }
+
+    /// Fill in every cell forced by a single row or column until nothing changes.
+    /// Returns false if some line can't be satisfied.
+    fn propagate(&self, grid: &mut Grid<Fountain>) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (row_idx, clue) in self.row_clues.iter().enumerate() {
+                let line = grid.rows[row_idx].clone();
+                let Some(solved_line) = solve_line(&line, clue) else {
+                    return false;
+                };
+                changed |= solved_line != line;
+                grid.rows[row_idx] = solved_line;
+            }
+            for (col_idx, clue) in self.col_clues.iter().enumerate() {
+                let line: Fountains = grid.get_col(col_idx).into_iter().copied().collect();
+                let Some(solved_line) = solve_line(&line, clue) else {
+                    return false;
+                };
+                if solved_line != line {
+                    changed = true;
+                    for (row_idx, fountain) in solved_line.into_iter().enumerate() {
+                        grid.rows[row_idx][col_idx] = fountain;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Whether any arrangement of the line matches its groups. Overflowing counts are plenty.
+fn is_line_possible(line: &[Fountain], groups: &[u8]) -> bool {
+    checked_possibility_count(line, groups).is_none_or(|count| count > 0)
+}
+
+/// Settle every unknown cell that's the same in all arrangements of the line.
+/// Returns `None` if there are no arrangements at all.
+fn solve_line(line: &[Fountain], groups: &[u8]) -> Option<Fountains> {
+    if !is_line_possible(line, groups) {
+        return None;
+    }
+    let mut solved = line.to_vec();
+    for idx in 0..line.len() {
+        if line[idx] != Unknown {
+            continue;
+        }
+        let mut trial = line.to_vec();
+        trial[idx] = Broken;
+        let can_be_broken = is_line_possible(&trial, groups);
+        trial[idx] = Good;
+        let can_be_good = is_line_possible(&trial, groups);
+        solved[idx] = match (can_be_broken, can_be_good) {
+            (true, false) => Broken,
+            (false, true) => Good,
+            _ => Unknown,
+        };
+    }
+    Some(solved)
+}
+
+/// Draw a grid of fountains as `.#?` characters, one row per line.
+pub fn render_grid(grid: &Grid<Fountain>) -> String {
+    grid.rows
+        .iter()
+        .map(|row| render_fountains(row))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unique() {
+        let puzzle = Nonogram::new(
+            vec![vec![1, 1], vec![5], vec![5], vec![3], vec![1]],
+            vec![vec![2], vec![4], vec![4], vec![4], vec![2]],
+        );
+        let NonogramSolution::Unique(grid) = puzzle.solve() else {
+            panic!("Expected a unique solution");
+        };
+        let expected = [".#.#.", "#####", "#####", ".###.", "..#.."];
+        assert_eq!(render_grid(&grid), expected.join("\n"));
+    }
+
+    #[test]
+    fn test_unique_needs_backtracking() {
+        // Line propagation alone stalls with 13 cells unknown, so this needs a guess.
+        let puzzle = Nonogram::new(
+            vec![vec![1, 2], vec![1, 1], vec![1, 2], vec![1, 1], vec![1, 2]],
+            vec![vec![3], vec![1, 1], vec![1], vec![3, 1], vec![1, 2]],
+        );
+        let (NonogramSolution::Unique(grid), guesses) = puzzle.solve_counting_guesses() else {
+            panic!("Expected a unique solution");
+        };
+        let expected = [".#.##", "#..#.", "#.##.", "#...#", ".#.##"];
+        assert_eq!(render_grid(&grid), expected.join("\n"));
+        assert!(guesses > 0);
+    }
+
+    #[test]
+    fn test_needs_backtracking() {
+        // Either diagonal works, and no single line forces anything.
+        let puzzle = Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        let NonogramSolution::Multiple(first, second) = puzzle.solve() else {
+            panic!("Expected multiple solutions");
+        };
+        assert_eq!(render_grid(&first), "#.\n.#");
+        assert_eq!(render_grid(&second), ".#\n#.");
+    }
+
+    #[test]
+    fn test_no_solution() {
+        let puzzle = Nonogram::new(vec![vec![2], vec![2]], vec![vec![1], vec![1]]);
+        assert_eq!(puzzle.solve(), NonogramSolution::None);
+    }
+
+    #[test]
+    fn test_empty_rows() {
+        let puzzle = Nonogram::new(vec![vec![2], vec![]], vec![vec![1], vec![1]]);
+        let NonogramSolution::Unique(grid) = puzzle.solve() else {
+            panic!("Expected a unique solution");
+        };
+        assert_eq!(render_grid(&grid), "##\n..");
+    }
+}
@@ -0,0 +1,152 @@
+use std::iter::Sum;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+/// A sensible worker count for this machine.
+pub fn default_workers() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Like `items.map(f).collect()`, but spread over `workers` threads.
+/// Results come back in the same order as the items, so the output matches the sequential path.
+pub fn par_map<T, U, F>(items: impl IntoIterator<Item = T>, workers: usize, f: F) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Sync,
+{
+    let batches = deal_out(items, workers);
+    if batches.len() <= 1 {
+        return batches
+            .into_iter()
+            .flatten()
+            .map(|(_, item)| f(item))
+            .collect();
+    }
+    let item_count = batches.iter().map(Vec::len).sum();
+    let f = &f;
+    let mut results: Vec<Option<U>> = (0..item_count).map(|_| None).collect();
+    thread::scope(|scope| {
+        let handles: Vec<_> = batches
+            .into_iter()
+            .map(|batch| {
+                scope.spawn(move || {
+                    batch
+                        .into_iter()
+                        .map(|(idx, item)| (idx, f(item)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (idx, result) in handle.join().expect("Worker thread panicked") {
+                results[idx] = Some(result);
+            }
+        }
+    });
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+/// Like `par_map`, but items are handed to the workers through a bounded channel as they are
+/// read, so a long input is never held in memory all at once. The items are pulled on the
+/// calling thread, so the iterator itself needn't be `Send`.
+pub fn par_map_stream<T, U, F>(items: impl IntoIterator<Item = T>, workers: usize, f: F) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Sync,
+{
+    let workers = workers.max(1);
+    if workers == 1 {
+        return items.into_iter().map(f).collect();
+    }
+    let f = &f;
+    let (item_tx, item_rx) = mpsc::sync_channel::<(usize, T)>(workers * 2);
+    let (result_tx, result_rx) = mpsc::channel::<(usize, U)>();
+    let item_rx = &Mutex::new(item_rx);
+    let mut results: Vec<Option<U>> = Vec::new();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let result_tx = result_tx.clone();
+            scope.spawn(move || loop {
+                let next = item_rx.lock().expect("Worker thread panicked").recv();
+                let Ok((idx, item)) = next else { break };
+                if result_tx.send((idx, f(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(result_tx);
+        for (idx, item) in items.into_iter().enumerate() {
+            item_tx.send((idx, item)).expect("Worker thread panicked");
+            results.push(None);
+        }
+        drop(item_tx);
+        for (idx, result) in result_rx {
+            results[idx] = Some(result);
+        }
+    });
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+/// Deals items out round-robin into one batch per worker thread, so expensive runs of
+/// neighbouring items get shared out. Never makes more batches than there are items.
+fn deal_out<T>(items: impl IntoIterator<Item = T>, workers: usize) -> Vec<Vec<(usize, T)>> {
+    let items: Vec<T> = items.into_iter().collect();
+    let workers = workers.min(items.len()).max(1);
+    let mut batches: Vec<Vec<(usize, T)>> = (0..workers).map(|_| Vec::new()).collect();
+    for (idx, item) in items.into_iter().enumerate() {
+        batches[idx % workers].push((idx, item));
+    }
+    batches
+}
+
+/// Like `items.map(f).sum()`, but spread over `workers` threads.
+pub fn par_sum<T, U, F>(items: impl IntoIterator<Item = T>, workers: usize, f: F) -> U
+where
+    T: Send,
+    U: Send + Sum,
+    F: Fn(T) -> U + Sync,
+{
+    par_map(items, workers, f).into_iter().sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_par_map_keeps_order() {
+        let expected: Vec<u64> = (0..100u64).map(|n| n * n).collect();
+        for workers in [0, 1, 3, 8, 200] {
+            assert_eq!(par_map(0..100u64, workers, |n| n * n), expected);
+        }
+        assert_eq!(par_map(Vec::<u64>::new(), 4, |n| n), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_fewer_items_than_workers() {
+        assert_eq!(deal_out(0..3, 8).len(), 3);
+        assert_eq!(deal_out(0..0, 8).len(), 1);
+        assert_eq!(deal_out(0..100, 8).len(), 8);
+        assert_eq!(par_map(0..3u64, 8, |n| n + 1), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_par_map_stream_keeps_order() {
+        let expected: Vec<u64> = (0..1000u64).map(|n| n * n).collect();
+        for workers in [0, 1, 3, 8] {
+            assert_eq!(par_map_stream(0..1000u64, workers, |n| n * n), expected);
+        }
+        assert_eq!(
+            par_map_stream(Vec::<u64>::new(), 4, |n| n),
+            Vec::<u64>::new()
+        );
+    }
+
+    #[test]
+    fn test_par_sum() {
+        let expected: u64 = (0..1000u64).map(|n| n * 3).sum();
+        assert_eq!(par_sum(0..1000u64, 4, |n| n * 3), expected);
+    }
+}
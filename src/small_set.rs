@@ -0,0 +1,82 @@
+/// A set of at most four items, stored inline without allocating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmallSet<T> {
+    items: [Option<T>; 4],
+    len: usize,
+}
+
+impl<T: Copy + Eq> SmallSet<T> {
+    pub fn new() -> Self {
+        Self {
+            items: [None; 4],
+            len: 0,
+        }
+    }
+
+    /// Adds an item, returning whether it wasn't already present.
+    pub fn insert(&mut self, item: T) -> bool {
+        if self.contains(&item) {
+            return false;
+        }
+        assert!(self.len < self.items.len(), "SmallSet is full");
+        self.items[self.len] = Some(item);
+        self.len += 1;
+        true
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        self.iter().any(|existing| existing == *item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.items[..self.len].iter().map(|item| item.unwrap())
+    }
+}
+
+impl<T: Copy + Eq> Default for SmallSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + Eq> FromIterator<T> for SmallSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for item in iter {
+            set.insert(item);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insert() {
+        let mut set = SmallSet::new();
+        assert!(set.is_empty());
+        assert!(set.insert(3));
+        assert!(set.insert(1));
+        assert!(!set.insert(3));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&1));
+        assert!(!set.contains(&2));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 1]);
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let set: SmallSet<char> = "abba".chars().collect();
+        assert_eq!(set.iter().collect::<String>(), "ab");
+    }
+}